@@ -0,0 +1,149 @@
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    highscores,
+    map_builder::FINAL_DEPTH,
+    morgue::RunStatistics,
+    palette::Colour,
+};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+
+const ACHIEVEMENT_FILE: &str = "./achievements.json";
+
+pub struct Achievement {
+    pub name: &'static str,
+    pub description: &'static str,
+    unlocked_by: fn(&RunStatistics) -> bool,
+}
+
+fn kills_of(stats: &RunStatistics, monster: &str) -> i32 {
+    stats.kills.get(monster).copied().unwrap_or(0)
+}
+
+/// Everything there is to unlock, all judged on a single run.
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        name: "First Blood",
+        description: "Kill a monster.",
+        unlocked_by: |s| !s.kills.is_empty(),
+    },
+    Achievement {
+        name: "Exterminator",
+        description: "Kill 50 monsters in one run.",
+        unlocked_by: |s| s.kills.values().sum::<i32>() >= 50,
+    },
+    Achievement {
+        name: "Dragonslayer",
+        description: "Slay a Fire Drake.",
+        unlocked_by: |s| kills_of(s, "Fire Drake") > 0,
+    },
+    Achievement {
+        name: "Lichbane",
+        description: "Destroy the Lich.",
+        unlocked_by: |s| kills_of(s, "The Lich") > 0,
+    },
+    Achievement {
+        name: "Delver",
+        description: "Reach depth 5.",
+        unlocked_by: |s| s.deepest_depth >= 5,
+    },
+    Achievement {
+        name: "Into the Lair",
+        description: "Reach the bottom of the dungeon.",
+        unlocked_by: |s| s.deepest_depth >= FINAL_DEPTH,
+    },
+    Achievement {
+        name: "Heavy Hitter",
+        description: "Deal 500 damage in one run.",
+        unlocked_by: |s| s.damage_dealt >= 500,
+    },
+    Achievement {
+        name: "Punching Bag",
+        description: "Take 300 damage in one run.",
+        unlocked_by: |s| s.damage_taken >= 300,
+    },
+    Achievement {
+        name: "Bottoms Up",
+        description: "Drink 10 potions in one run.",
+        unlocked_by: |s| s.potions_drunk >= 10,
+    },
+    Achievement {
+        name: "Watch Your Step",
+        description: "Trigger 5 traps in one run.",
+        unlocked_by: |s| s.traps_triggered >= 5,
+    },
+    Achievement {
+        name: "Cartographer",
+        description: "Reveal 5000 tiles in one run.",
+        unlocked_by: |s| s.tiles_revealed >= 5000,
+    },
+    Achievement {
+        name: "Long Walk",
+        description: "Walk 3000 steps in one run.",
+        unlocked_by: |s| s.steps_walked >= 3000,
+    },
+    Achievement {
+        name: "Spellslinger",
+        description: "Cast 25 spells in one run.",
+        unlocked_by: |s| s.spells_cast.values().sum::<i32>() >= 25,
+    },
+    Achievement {
+        name: "Survivor",
+        description: "Last 2000 turns in one run.",
+        unlocked_by: |s| s.turns >= 2000,
+    },
+];
+
+/// Achievements unlocked so far, in any run, with the date each was first earned.
+/// Kept in its own file so it outlives saves and deaths.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Achievements {
+    pub unlocked: BTreeMap<String, String>,
+}
+
+impl Achievements {
+    pub fn load() -> Achievements {
+        fs::read_to_string(ACHIEVEMENT_FILE)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            if fs::write(ACHIEVEMENT_FILE, data).is_err() {
+                rltk::console::log("Unable to write the achievements file");
+            }
+        }
+    }
+}
+
+/// Unlocks anything the current run has earned, announcing it in the log.
+pub fn check_achievements(ecs: &World) {
+    let stats = ecs.fetch::<RunStatistics>();
+    let mut achievements = ecs.write_resource::<Achievements>();
+    let mut log = ecs.write_resource::<GameLog>();
+
+    let mut changed = false;
+    for achievement in ACHIEVEMENTS.iter() {
+        if !achievements.unlocked.contains_key(achievement.name)
+            && (achievement.unlocked_by)(&stats)
+        {
+            achievements
+                .unlocked
+                .insert(achievement.name.to_string(), highscores::today());
+            log.add(
+                LogMessage::new(LogCategory::System)
+                    .text("Achievement unlocked: ")
+                    .colour(achievement.name, Colour::Gold)
+                    .text("!"),
+            );
+            changed = true;
+        }
+    }
+    if changed {
+        achievements.save();
+    }
+}
@@ -0,0 +1,389 @@
+use super::{
+    encumbrance_system,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    map::TileType,
+    player, CombatStats, EntryTrigger, Hidden, Item, Map, Monster, Name, Position, RunState,
+    WantsToPickupItem,
+};
+use rltk::{Algorithm2D, BaseMap, DijkstraMap, Point};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// How far, in steps, autoexplore will look for somewhere new.
+const EXPLORE_SEARCH_DEPTH: f32 = 2000.0;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AutoMode {
+    Explore,
+    Travel { destination: Point },
+}
+
+/// Moves the player on their behalf, one step per turn, until something worth their attention
+/// happens.
+#[derive(Default)]
+pub struct AutoMove {
+    pub mode: Option<AutoMode>,
+    /// Walk over to visible items and pick them up, rather than stopping when one is seen.
+    pub pickup_items: bool,
+    last_hp: i32,
+    /// Items and traps already seen, so only new ones interrupt.
+    known: HashSet<Entity>,
+    /// Items too heavy to pick up, which autoexplore no longer walks to.
+    refused: HashSet<Entity>,
+}
+
+impl AutoMove {
+    pub fn new(pickup_items: bool) -> AutoMove {
+        AutoMove {
+            pickup_items,
+            ..Default::default()
+        }
+    }
+}
+
+/// Whatever the player can see that is worth stopping for: creatures, then items and traps.
+fn visible_interests(ecs: &World) -> (Vec<Entity>, Vec<Entity>) {
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let monsters = ecs.read_storage::<Monster>();
+    let items = ecs.read_storage::<Item>();
+    let traps = ecs.read_storage::<EntryTrigger>();
+
+    let mut seen_monsters = Vec::new();
+    let mut seen_things = Vec::new();
+    for (entity, pos, _hidden) in (&entities, &positions, !&hidden).join() {
+        if !map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+            continue;
+        }
+        if monsters.get(entity).is_some() {
+            seen_monsters.push(entity);
+        } else if items.get(entity).is_some() || traps.get(entity).is_some() {
+            seen_things.push(entity);
+        }
+    }
+    (seen_monsters, seen_things)
+}
+
+fn player_hp(ecs: &World) -> i32 {
+    let player_entity = ecs.fetch::<Entity>();
+    ecs.read_storage::<CombatStats>()
+        .get(*player_entity)
+        .map_or(0, |stats| stats.hp)
+}
+
+pub fn is_active(ecs: &World) -> bool {
+    ecs.fetch::<AutoMove>().mode.is_some()
+}
+
+/// Stops moving automatically, saying why.
+pub fn stop(ecs: &mut World, reason: LogMessage) {
+    ecs.write_resource::<AutoMove>().mode = None;
+    ecs.write_resource::<GameLog>().add(reason);
+}
+
+/// Starts moving automatically. Refuses while monsters are in sight, or to travel somewhere the
+/// player has never seen.
+pub fn start(ecs: &mut World, mode: AutoMode) -> RunState {
+    if let AutoMode::Travel { destination } = mode {
+        let known = {
+            let map = ecs.fetch::<Map>();
+            destination.x >= 0
+                && destination.x < map.width
+                && destination.y >= 0
+                && destination.y < map.height
+                && map.revealed_tiles[map.xy_idx(destination.x, destination.y)]
+        };
+        if !known {
+            ecs.write_resource::<GameLog>()
+                .push(LogCategory::System, "You don't know the way there.");
+            return RunState::AwaitingInput;
+        }
+    }
+    let (monsters, things) = visible_interests(ecs);
+    if !monsters.is_empty() {
+        ecs.write_resource::<GameLog>()
+            .push(LogCategory::System, "Not with enemies in sight!");
+        return RunState::AwaitingInput;
+    }
+    let hp = player_hp(ecs);
+    let mut auto = ecs.write_resource::<AutoMove>();
+    auto.mode = Some(mode);
+    auto.last_hp = hp;
+    auto.known = things.into_iter().collect();
+    auto.refused.clear();
+    RunState::AwaitingInput
+}
+
+/// Checks whether anything should interrupt automatic movement, stopping it if so.
+fn interrupted(ecs: &mut World) -> bool {
+    let (monsters, things) = visible_interests(ecs);
+    let hp = player_hp(ecs);
+    let names = |entity: Entity| {
+        ecs.read_storage::<Name>()
+            .get(entity)
+            .map_or("something".to_string(), |n| n.name.clone())
+    };
+
+    let reason = if let Some(monster) = monsters.first() {
+        Some(
+            LogMessage::new(LogCategory::System)
+                .text("You see ")
+                .npc(names(*monster))
+                .text(" and stop."),
+        )
+    } else if hp < ecs.fetch::<AutoMove>().last_hp {
+        Some(LogMessage::new(LogCategory::System).text("You are hurt, and stop."))
+    } else {
+        let auto = ecs.fetch::<AutoMove>();
+        let items = ecs.read_storage::<Item>();
+        things
+            .iter()
+            // Items being collected are walked to rather than stopped for
+            .filter(|e| !auto.pickup_items || items.get(**e).is_none())
+            .find(|e| !auto.known.contains(e))
+            .map(|thing| {
+                LogMessage::new(LogCategory::System)
+                    .text("You spot ")
+                    .item(names(*thing))
+                    .text(" and stop.")
+            })
+    };
+
+    {
+        let mut auto = ecs.write_resource::<AutoMove>();
+        auto.last_hp = hp;
+        auto.known.extend(things);
+    }
+    match reason {
+        Some(reason) => {
+            stop(ecs, reason);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Sets off for the nearest down staircase the player knows about.
+pub fn travel_to_stairs(ecs: &mut World) -> RunState {
+    let stairs = {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<Point>();
+        (0..map.tiles.len())
+            .filter(|idx| map.revealed_tiles[*idx] && map.tiles[*idx] == TileType::DownStairs)
+            .map(|idx| map.index_to_point2d(idx))
+            .min_by(|a, b| {
+                let da = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *a);
+                let db = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *b);
+                da.total_cmp(&db)
+            })
+    };
+    match stairs {
+        Some(destination) => start(ecs, AutoMode::Travel { destination }),
+        None => {
+            ecs.write_resource::<GameLog>()
+                .push(LogCategory::System, "You haven't found a way down yet.");
+            RunState::AwaitingInput
+        }
+    }
+}
+
+/// Tiles with a trap the player has found.
+fn known_traps(ecs: &World) -> HashSet<usize> {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let traps = ecs.read_storage::<EntryTrigger>();
+    let hidden = ecs.read_storage::<Hidden>();
+    (&positions, &traps, !&hidden)
+        .join()
+        .map(|(pos, _, _)| map.xy_idx(pos.x, pos.y))
+        .collect()
+}
+
+/// The map as far as the player knows it, so automatic movement never walks onto a trap the
+/// player has found, unless it is where they asked to go. Travel also never routes through
+/// unexplored places.
+struct KnownMap<'a> {
+    map: &'a Map,
+    traps: HashSet<usize>,
+    destination: Option<usize>,
+    revealed_only: bool,
+}
+
+impl<'a> BaseMap for KnownMap<'a> {
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.map
+            .get_available_exits(idx)
+            .into_iter()
+            .filter(|(exit, _)| !self.revealed_only || self.map.revealed_tiles[*exit])
+            .filter(|(exit, _)| !self.traps.contains(exit) || self.destination == Some(*exit))
+            .collect()
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
+
+impl<'a> Algorithm2D for KnownMap<'a> {
+    fn dimensions(&self) -> Point {
+        self.map.dimensions()
+    }
+}
+
+/// The first step along the shortest known route to `destination`.
+fn travel_step(ecs: &World, destination: Point) -> Option<usize> {
+    let traps = known_traps(ecs);
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    let end = map.xy_idx(destination.x, destination.y);
+    let path = rltk::a_star_search(
+        map.xy_idx(player_pos.x, player_pos.y),
+        end,
+        &KnownMap {
+            map: &map,
+            traps,
+            destination: Some(end),
+            revealed_only: true,
+        },
+    );
+    if path.success && path.steps.len() > 1 {
+        Some(path.steps[1])
+    } else {
+        None
+    }
+}
+
+/// Picks up an item underfoot, if autoexplore is collecting them.
+fn pickup_underfoot(ecs: &mut World) -> bool {
+    if !ecs.fetch::<AutoMove>().pickup_items {
+        return false;
+    }
+    let player_entity = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    let item = {
+        let entities = ecs.entities();
+        let items = ecs.read_storage::<Item>();
+        let positions = ecs.read_storage::<Position>();
+        let auto = ecs.fetch::<AutoMove>();
+        (&entities, &items, &positions)
+            .join()
+            .find(|(entity, _, pos)| {
+                pos.x == player_pos.x && pos.y == player_pos.y && !auto.refused.contains(entity)
+            })
+            .map(|(entity, _, _)| entity)
+    };
+    match item {
+        Some(item) if !encumbrance_system::can_carry(ecs, player_entity, item) => {
+            let name = ecs
+                .read_storage::<Name>()
+                .get(item)
+                .map_or("item".to_string(), |n| n.name.clone());
+            ecs.write_resource::<GameLog>().add(
+                LogMessage::new(LogCategory::Item)
+                    .text("The ")
+                    .item(name)
+                    .text(" is too heavy to carry, so you leave it."),
+            );
+            ecs.write_resource::<AutoMove>().refused.insert(item);
+            false
+        }
+        Some(item) => {
+            ecs.write_storage::<WantsToPickupItem>()
+                .insert(
+                    player_entity,
+                    WantsToPickupItem {
+                        collected_by: player_entity,
+                        item,
+                        count: 1,
+                    },
+                )
+                .expect("Unable to insert want to pickup");
+            true
+        }
+        None => false,
+    }
+}
+
+/// The first step towards the nearest unexplored floor, or visible item if collecting them.
+fn explore_step(ecs: &World) -> Option<usize> {
+    let traps = known_traps(ecs);
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    let mut targets: Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| !map.revealed_tiles[*idx] && map.tiles[*idx] != TileType::Wall)
+        .collect();
+    let auto = ecs.fetch::<AutoMove>();
+    if auto.pickup_items {
+        let entities = ecs.entities();
+        let items = ecs.read_storage::<Item>();
+        let positions = ecs.read_storage::<Position>();
+        let hidden = ecs.read_storage::<Hidden>();
+        for (item, _item, pos, _hidden) in (&entities, &items, &positions, !&hidden).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            if map.visible_tiles[idx] && !auto.refused.contains(&item) && !traps.contains(&idx) {
+                targets.push(idx);
+            }
+        }
+    }
+
+    let known = KnownMap {
+        map: &map,
+        traps,
+        destination: None,
+        revealed_only: false,
+    };
+    let flow = DijkstraMap::new(
+        map.width,
+        map.height,
+        &targets,
+        &known,
+        EXPLORE_SEARCH_DEPTH,
+    );
+    DijkstraMap::find_lowest_exit(&flow, map.xy_idx(player_pos.x, player_pos.y), &known)
+        .filter(|idx| flow.map[*idx] < f32::MAX)
+}
+
+/// Takes the player's turn for them, or stops and hands control back.
+pub fn step(ecs: &mut World) -> RunState {
+    let mode = match ecs.fetch::<AutoMove>().mode {
+        Some(mode) => mode,
+        None => return RunState::AwaitingInput,
+    };
+    if interrupted(ecs) {
+        return RunState::AwaitingInput;
+    }
+    let next = match mode {
+        AutoMode::Explore => {
+            if pickup_underfoot(ecs) {
+                return RunState::PlayerTurn;
+            }
+            explore_step(ecs).ok_or("There is nothing left to explore.")
+        }
+        AutoMode::Travel { destination } => {
+            if *ecs.fetch::<Point>() == destination {
+                Err("You arrive.")
+            } else {
+                travel_step(ecs, destination).ok_or("You can't find a way there.")
+            }
+        }
+    };
+    match next {
+        Err(reason) => {
+            stop(ecs, LogMessage::new(LogCategory::System).text(reason));
+            RunState::AwaitingInput
+        }
+        Ok(next) => {
+            let (dx, dy) = {
+                let map = ecs.fetch::<Map>();
+                let player_pos = ecs.fetch::<Point>();
+                (
+                    next as i32 % map.width - player_pos.x,
+                    next as i32 / map.width - player_pos.y,
+                )
+            };
+            player::try_move_player(dx, dy, ecs)
+        }
+    }
+}
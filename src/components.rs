@@ -57,15 +57,24 @@ pub struct WantsToMelee {
 #[derive(Component, Debug, ConvertSaveload)]
 pub struct SufferDamage {
     pub amount: Vec<i32>,
+    /// Whether the last hit came from the player, who gets the kill if it was fatal.
+    pub from_player: bool,
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(
+        store: &mut WriteStorage<SufferDamage>,
+        victim: Entity,
+        amount: i32,
+        from_player: bool,
+    ) {
         if let Some(suffering) = store.get_mut(victim) {
             suffering.amount.push(amount);
+            suffering.from_player = from_player;
         } else {
             let dmg = SufferDamage {
                 amount: vec![amount],
+                from_player,
             };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
@@ -89,6 +98,8 @@ pub struct InBackpack {
 pub struct WantsToPickupItem {
     pub collected_by: Entity,
     pub item: Entity,
+    /// How many to pick up: `item`, and more of the same name lying with it.
+    pub count: i32,
 }
 
 #[derive(Component, Debug, ConvertSaveload)]
@@ -100,6 +111,8 @@ pub struct WantsToUseItem {
 #[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToDropItem {
     pub item: Entity,
+    /// How many to drop: `item`, and more of the same name from the same backpack.
+    pub count: i32,
 }
 
 #[derive(Component, Serialize, Deserialize, Clone)]
@@ -139,6 +152,8 @@ pub struct SerializeMe;
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
     pub map: super::map::Map,
+    #[serde(default)]
+    pub stats: super::morgue::RunStatistics,
 }
 
 #[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -195,6 +210,49 @@ pub struct HungerClock {
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct ProvidesFood {}
 
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ItemWeight {
+    pub pounds: i32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum BurdenState {
+    Unburdened,
+    Burdened,
+    Overloaded,
+}
+
+/// A monster that picks up, wields and uses items.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct CarriesItems {}
+
+/// Holds items: a chest on the floor, or a bag carried in the backpack.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Container {}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct InContainer {
+    pub container: Entity,
+}
+
+/// A container that has to be forced open first.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Locked {}
+
+/// A container that hurts whoever opens it, once.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Trapped {
+    pub damage: i32,
+}
+
+/// How much an entity is carrying, against how much it can carry.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Encumbrance {
+    pub carried: i32,
+    pub capacity: i32,
+    pub state: BurdenState,
+}
+
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct MagicMapper {}
 
@@ -209,3 +267,119 @@ pub struct EntityMoved {}
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct SingleActivation {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Purse {
+    pub gold: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Gold {
+    pub amount: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ItemValue {
+    pub gold: i32,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum VendorKind {
+    Blacksmith,
+    Alchemist,
+    Tavern,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Vendor {
+    pub kind: VendorKind,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Bystander {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct TownPortal {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Portal {
+    pub depth: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct LootTable {
+    pub table: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct LeavesCorpse {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Mana {
+    pub current: i32,
+    pub max: i32,
+    /// Turns until the next point of mana comes back.
+    pub regen_timer: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct KnownSpells {
+    pub spells: Vec<String>,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct TeachesSpell {
+    pub spell: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct WantsToCastSpell {
+    pub spell: String,
+    pub target: Option<rltk::Point>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum AbilityEffect {
+    /// Casts a spell at the player.
+    CastSpell {
+        spell: String,
+    },
+    /// Casts a spell at the most wounded ally in view.
+    SupportSpell {
+        spell: String,
+    },
+    Summon {
+        minion: String,
+        count: i32,
+    },
+    /// Blinks to a random spot well away from the player.
+    Teleport,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MonsterAbility {
+    pub effect: AbilityEffect,
+    pub cooldown: i32,
+    pub cooldown_remaining: i32,
+    /// Distance to the target (the ally, for support spells; otherwise the player).
+    pub min_range: f32,
+    pub max_range: f32,
+    /// Only used at or below this percentage of hit points: the ally's for support spells,
+    /// the monster's own otherwise.
+    pub hp_threshold: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MonsterAbilities {
+    pub abilities: Vec<MonsterAbility>,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct WantsToSummon {
+    pub minion: String,
+    pub count: i32,
+}
+
+/// The item a run is won by bringing back to town.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct QuestItem {}
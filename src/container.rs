@@ -0,0 +1,201 @@
+use super::{
+    encumbrance_system,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    gui::ContainerMode,
+    morgue::{self, RunStatistics},
+    CombatStats, Encumbrance, InBackpack, InContainer, ItemWeight, Locked, Name, RunState,
+    SufferDamage, Trapped,
+};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+/// Everything inside a container.
+pub fn contents(ecs: &World, container: Entity) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let in_container = ecs.read_storage::<InContainer>();
+    (&entities, &in_container)
+        .join()
+        .filter(|(_, inside)| inside.container == container)
+        .map(|(item, _)| item)
+        .collect()
+}
+
+/// Whether the player is carrying the container, so moving items in and out of it doesn't
+/// change how much they carry.
+fn carried(ecs: &World, container: Entity) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.read_storage::<InBackpack>()
+        .get(container)
+        .is_some_and(|pack| pack.owner == player_entity)
+}
+
+fn name_of(ecs: &World, entity: Entity) -> String {
+    ecs.read_storage::<Name>()
+        .get(entity)
+        .map_or("container".to_string(), |n| n.name.clone())
+}
+
+/// Up to `count` items named like `item`, from among `items`, starting with `item` itself.
+fn same_name(ecs: &World, item: Entity, count: i32, items: Vec<Entity>) -> Vec<Entity> {
+    let name = name_of(ecs, item);
+    let names = ecs.read_storage::<Name>();
+    let mut matching = vec![item];
+    matching.extend(
+        items
+            .into_iter()
+            .filter(|other| *other != item && names.get(*other).is_some_and(|n| n.name == name))
+            .take(i32::max(0, count - 1) as usize),
+    );
+    matching
+}
+
+fn log_moved(ecs: &World, verb: &str, name: &str, count: usize, place: &str) {
+    let message = LogMessage::new(LogCategory::Item)
+        .text(format!("You {} the ", verb))
+        .item(name);
+    let message = if count > 1 {
+        message.text(format!(" (x{})", count))
+    } else {
+        message
+    };
+    ecs.fetch_mut::<GameLog>()
+        .add(message.text(format!(" {}.", place)));
+}
+
+/// Opens a container for the player. A locked one has to be forced first, which takes a turn
+/// and may fail; a trapped one goes off in the player's face, then opens.
+pub fn open(ecs: &mut World, container: Entity) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = name_of(ecs, container);
+
+    if ecs.read_storage::<Locked>().contains(container) {
+        let power = ecs
+            .read_storage::<CombatStats>()
+            .get(player_entity)
+            .map_or(0, |stats| stats.power);
+        let roll = ecs
+            .write_resource::<RandomNumberGenerator>()
+            .roll_dice(1, 20);
+        if roll + power < 15 {
+            ecs.fetch_mut::<GameLog>().push(
+                LogCategory::Item,
+                format!("The {} is locked. You fail to force it open.", name),
+            );
+            return RunState::PlayerTurn;
+        }
+        ecs.write_storage::<Locked>().remove(container);
+        ecs.fetch_mut::<GameLog>().push(
+            LogCategory::Item,
+            format!("You force the lock on the {}.", name),
+        );
+    }
+
+    if let Some(trap) = ecs.write_storage::<Trapped>().remove(container) {
+        SufferDamage::new_damage(
+            &mut ecs.write_storage::<SufferDamage>(),
+            player_entity,
+            trap.damage,
+            false,
+        );
+        ecs.write_resource::<RunStatistics>().last_hurt_by = Some(format!("a trapped {}", name));
+        ecs.fetch_mut::<GameLog>().push(
+            LogCategory::Trap,
+            format!(
+                "A needle springs out of the {}! You suffer {} hp damage.",
+                name, trap.damage
+            ),
+        );
+    }
+
+    RunState::ShowContainer {
+        container,
+        mode: ContainerMode::Take,
+        page: 0,
+    }
+}
+
+/// Takes up to `count` of `item`'s stack out of a container and into the player's backpack,
+/// stopping if the load gets too heavy.
+pub fn take(ecs: &mut World, container: Entity, item: Entity, count: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = name_of(ecs, item);
+    let items = same_name(ecs, item, count, contents(ecs, container));
+    let weighed = !carried(ecs, container);
+
+    let mut taken = 0;
+    for item in items {
+        let pounds = ecs
+            .read_storage::<ItemWeight>()
+            .get(item)
+            .map_or(0, |w| w.pounds);
+        if weighed {
+            if let Some(load) = ecs.write_storage::<Encumbrance>().get_mut(player_entity) {
+                if load.carried + pounds > encumbrance_system::weight_limit(load.capacity) {
+                    ecs.fetch_mut::<GameLog>().add(
+                        LogMessage::new(LogCategory::Item)
+                            .text("The ")
+                            .item(&name)
+                            .text(" is too heavy to carry with everything else."),
+                    );
+                    break;
+                }
+                load.carried += pounds;
+            }
+            morgue::tally(
+                &mut ecs.write_resource::<RunStatistics>().items_found,
+                &name,
+            );
+        }
+        ecs.write_storage::<InContainer>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(
+                item,
+                InBackpack {
+                    owner: player_entity,
+                },
+            )
+            .expect("Unable to insert backpack entry");
+        taken += 1;
+    }
+
+    if taken > 0 {
+        let place = format!("out of the {}", name_of(ecs, container));
+        log_moved(ecs, "take", &name, taken, &place);
+    }
+}
+
+/// Puts up to `count` of `item`'s stack from the player's backpack into a container.
+pub fn put(ecs: &mut World, container: Entity, item: Entity, count: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = name_of(ecs, item);
+    let carried_items: Vec<Entity> = {
+        let entities = ecs.entities();
+        let backpack = ecs.read_storage::<InBackpack>();
+        (&entities, &backpack)
+            .join()
+            .filter(|(_, pack)| pack.owner == player_entity)
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+    let items = same_name(ecs, item, count, carried_items);
+    let weighed = !carried(ecs, container);
+
+    for item in items.iter() {
+        if weighed {
+            let pounds = ecs
+                .read_storage::<ItemWeight>()
+                .get(*item)
+                .map_or(0, |w| w.pounds);
+            if let Some(load) = ecs.write_storage::<Encumbrance>().get_mut(player_entity) {
+                load.carried -= pounds;
+            }
+        }
+        ecs.write_storage::<InBackpack>().remove(*item);
+        ecs.write_storage::<InContainer>()
+            .insert(*item, InContainer { container })
+            .expect("Unable to insert container entry");
+    }
+
+    let place = format!("in the {}", name_of(ecs, container));
+    log_moved(ecs, "put", &name, items.len(), &place);
+}
@@ -1,5 +1,10 @@
-use super::{gamelog::GameLog, CombatStats, Map, Name, Player, Position, RunState, SufferDamage};
-use rltk::console;
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    morgue::{self, RunStatistics},
+    spawner, CombatStats, Equipped, InBackpack, LeavesCorpse, LootTable, Map, Name, Player,
+    Position, Purse, RunState, SufferDamage,
+};
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 
 pub struct DamageSystem {}
@@ -11,13 +16,37 @@ impl<'a> System<'a> for DamageSystem {
         ReadStorage<'a, Position>,
         WriteExpect<'a, Map>,
         Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStatistics>,
+        ReadStorage<'a, Name>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
+        let (
+            mut stats,
+            mut damage,
+            positions,
+            mut map,
+            entities,
+            player_entity,
+            mut run_stats,
+            names,
+        ) = data;
 
         for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+            let total = damage.amount.iter().sum::<i32>();
+            let was_alive = stats.hp > 0;
+            stats.hp -= total;
+            if entity == *player_entity {
+                run_stats.damage_taken += total;
+            }
+            // Only the player's own kills count towards their score
+            if was_alive && stats.hp < 1 && damage.from_player && entity != *player_entity {
+                if let Some(name) = names.get(entity) {
+                    morgue::tally(&mut run_stats.kills, &name.name);
+                    run_stats.xp += spawner::xp_value(&name.name);
+                }
+            }
             let pos = positions.get(entity);
             if let Some(pos) = pos {
                 let idx = map.xy_idx(pos.x, pos.y);
@@ -31,12 +60,23 @@ impl<'a> System<'a> for DamageSystem {
 
 pub fn delete_the_dead(ecs: &mut World) -> Option<RunState> {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut gold_drops: Vec<(i32, i32, i32)> = Vec::new();
+    let mut loot_drops: Vec<(usize, String)> = Vec::new();
+    let mut corpses: Vec<(i32, i32, String)> = Vec::new();
+    let mut dropped_items: Vec<(Entity, i32, i32)> = Vec::new();
     let mut result: Option<RunState> = None;
     // Using a scope to make the borrow checker happy
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let purses = ecs.read_storage::<Purse>();
+        let loot_tables = ecs.read_storage::<LootTable>();
+        let leaves_corpse = ecs.read_storage::<LeavesCorpse>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let equipped = ecs.read_storage::<Equipped>();
+        let map = ecs.fetch::<Map>();
         let entities = ecs.entities();
         let mut log = ecs.write_resource::<GameLog>();
         for (entity, stats) in (&entities, &combat_stats).join() {
@@ -46,13 +86,47 @@ pub fn delete_the_dead(ecs: &mut World) -> Option<RunState> {
                     None => {
                         let victim_name = names.get(entity);
                         if let Some(victim_name) = victim_name {
-                            log.entries.push(format!("{} is dead", &victim_name.name));
+                            log.add(
+                                LogMessage::new(LogCategory::Combat)
+                                    .npc(&victim_name.name)
+                                    .text(" is dead"),
+                            );
+                        }
+                        if let (Some(pos), Some(purse)) =
+                            (positions.get(entity), purses.get(entity))
+                        {
+                            if purse.gold > 0 {
+                                gold_drops.push((pos.x, pos.y, purse.gold));
+                            }
+                        }
+                        if let Some(pos) = positions.get(entity) {
+                            if let Some(loot) = loot_tables.get(entity) {
+                                loot_drops.push((map.xy_idx(pos.x, pos.y), loot.table.clone()));
+                            }
+                            if let (Some(_), Some(victim_name)) =
+                                (leaves_corpse.get(entity), victim_name)
+                            {
+                                corpses.push((pos.x, pos.y, victim_name.name.clone()));
+                            }
+                            // Everything carried or worn falls where it died
+                            for (item, _) in (&entities, &backpack)
+                                .join()
+                                .filter(|(_, pack)| pack.owner == entity)
+                            {
+                                dropped_items.push((item, pos.x, pos.y));
+                            }
+                            for (item, _) in (&entities, &equipped)
+                                .join()
+                                .filter(|(_, worn)| worn.owner == entity)
+                            {
+                                dropped_items.push((item, pos.x, pos.y));
+                            }
                         }
                         dead.push(entity)
                     }
                     Some(_) => {
                         //let mut runstate = ecs.write_resource::<RunState>();
-                        result = Some(RunState::GameOver);
+                        result = Some(RunState::GameOver { scroll: 0 });
                     }
                 }
             }
@@ -63,5 +137,40 @@ pub fn delete_the_dead(ecs: &mut World) -> Option<RunState> {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
 
+    for (item, x, y) in dropped_items {
+        ecs.write_storage::<InBackpack>().remove(item);
+        ecs.write_storage::<Equipped>().remove(item);
+        ecs.write_storage::<Position>()
+            .insert(item, Position { x, y })
+            .expect("Unable to insert position");
+    }
+
+    for (x, y, amount) in gold_drops {
+        spawner::gold(ecs, x, y, amount);
+    }
+
+    // Roll each victim's loot table and drop the result where it fell
+    for (idx, table) in loot_drops {
+        let drops = {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            let (table, rolls) = spawner::loot_table(&table);
+            table.roll_distinct(rolls, &mut rng)
+        };
+        for drop in drops.iter() {
+            spawner::spawn_entity(ecs, idx, drop);
+        }
+    }
+
+    // Not every body survives the fight intact
+    for (x, y, victim) in corpses {
+        let intact = ecs
+            .write_resource::<RandomNumberGenerator>()
+            .roll_dice(1, 3)
+            > 1;
+        if intact {
+            spawner::corpse(ecs, x, y, &victim);
+        }
+    }
+
     return result;
 }
@@ -0,0 +1,119 @@
+use super::{
+    gamelog::{GameLog, LogCategory},
+    BurdenState, CombatStats, Encumbrance, Equipped, InBackpack, InContainer, ItemWeight,
+};
+use specs::prelude::*;
+
+/// How much a creature can carry before it starts to slow down: stronger hitters carry more.
+pub fn capacity(power: i32) -> i32 {
+    10 + power * 4
+}
+
+/// Nothing can be picked up that would take the load past this.
+pub fn weight_limit(capacity: i32) -> i32 {
+    capacity * 2
+}
+
+/// Whether `who` could take `item`, bag contents and all, without going over their limit.
+pub fn can_carry(ecs: &World, who: Entity, item: Entity) -> bool {
+    let weights = ecs.read_storage::<ItemWeight>();
+    let pounds = weights.get(item).map_or(0, |w| w.pounds)
+        + (&ecs.read_storage::<InContainer>(), &weights)
+            .join()
+            .filter(|(inside, _)| inside.container == item)
+            .map(|(_, weight)| weight.pounds)
+            .sum::<i32>();
+    match ecs.read_storage::<Encumbrance>().get(who) {
+        Some(load) => load.carried + pounds <= weight_limit(load.capacity),
+        None => true,
+    }
+}
+
+fn burden(carried: i32, capacity: i32) -> BurdenState {
+    if carried > capacity * 3 / 2 {
+        BurdenState::Overloaded
+    } else if carried > capacity {
+        BurdenState::Burdened
+    } else {
+        BurdenState::Unburdened
+    }
+}
+
+/// Melee power lost to carrying too much.
+pub fn attack_penalty(state: BurdenState) -> i32 {
+    match state {
+        BurdenState::Unburdened => 0,
+        BurdenState::Burdened => 1,
+        BurdenState::Overloaded => 2,
+    }
+}
+
+/// Adds up what everyone with an `Encumbrance` carries and wears, bags and all, and works out
+/// how burdened they are.
+pub struct EncumbranceSystem {}
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, Encumbrance>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, ItemWeight>,
+        ReadStorage<'a, InContainer>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut log,
+            mut encumbrance,
+            combat_stats,
+            backpack,
+            equipped,
+            weights,
+            in_container,
+        ) = data;
+
+        for (entity, load, stats) in (&entities, &mut encumbrance, &combat_stats).join() {
+            let carried: i32 = (&backpack, &weights)
+                .join()
+                .filter(|(pack, _)| pack.owner == entity)
+                .map(|(_, weight)| weight.pounds)
+                .sum::<i32>()
+                + (&equipped, &weights)
+                    .join()
+                    .filter(|(worn, _)| worn.owner == entity)
+                    .map(|(_, weight)| weight.pounds)
+                    .sum::<i32>()
+                + (&in_container, &weights)
+                    .join()
+                    .filter(|(inside, _)| {
+                        backpack
+                            .get(inside.container)
+                            .is_some_and(|pack| pack.owner == entity)
+                    })
+                    .map(|(_, weight)| weight.pounds)
+                    .sum::<i32>();
+            load.carried = carried;
+            load.capacity = capacity(stats.power);
+
+            let state = burden(carried, load.capacity);
+            if state != load.state && entity == *player_entity {
+                log.push(
+                    LogCategory::System,
+                    match state {
+                        BurdenState::Unburdened => "You are no longer burdened.",
+                        BurdenState::Burdened => "You are burdened by your load.",
+                        BurdenState::Overloaded => "You are overloaded, and can barely move!",
+                    },
+                );
+            }
+            load.state = state;
+        }
+    }
+}
@@ -1,3 +1,181 @@
+use super::palette::Colour;
+use std::collections::VecDeque;
+
+/// How many entries the log keeps before forgetting the oldest, unless told otherwise.
+const MAX_LOG_ENTRIES: usize = 500;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LogCategory {
+    Combat,
+    Item,
+    Trap,
+    System,
+}
+
+impl LogCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogCategory::Combat => "Combat",
+            LogCategory::Item => "Item",
+            LogCategory::Trap => "Trap",
+            LogCategory::System => "System",
+        }
+    }
+}
+
+/// A run of text printed in one colour, chosen by the palette when it is drawn.
+#[derive(Clone)]
+pub struct LogFragment {
+    pub colour: Colour,
+    pub text: String,
+}
+
+/// A message being put together a piece at a time, before it goes into the log.
+pub struct LogMessage {
+    category: LogCategory,
+    fragments: Vec<LogFragment>,
+}
+
+impl LogMessage {
+    pub fn new(category: LogCategory) -> LogMessage {
+        LogMessage {
+            category,
+            fragments: Vec::new(),
+        }
+    }
+
+    pub fn colour<S: ToString>(mut self, text: S, colour: Colour) -> LogMessage {
+        self.fragments.push(LogFragment {
+            colour,
+            text: text.to_string(),
+        });
+        self
+    }
+
+    pub fn text<S: ToString>(self, text: S) -> LogMessage {
+        self.colour(text, Colour::Text)
+    }
+
+    /// The name of a monster or other creature.
+    pub fn npc<S: ToString>(self, name: S) -> LogMessage {
+        self.colour(name, Colour::Danger)
+    }
+
+    pub fn item<S: ToString>(self, name: S) -> LogMessage {
+        self.colour(name, Colour::Item)
+    }
+
+    pub fn player<S: ToString>(self, name: S) -> LogMessage {
+        self.colour(name, Colour::Player)
+    }
+
+    /// A creature that may or may not be the player.
+    pub fn creature<S: ToString>(self, name: S, is_player: bool) -> LogMessage {
+        if is_player {
+            self.player(name)
+        } else {
+            self.npc(name)
+        }
+    }
+}
+
+/// One line of the log. Identical messages in a row share an entry, counting the repeats.
+#[derive(Clone)]
+pub struct LogEntry {
+    pub category: LogCategory,
+    /// The turn the message was last logged on.
+    pub turn: i32,
+    pub fragments: Vec<LogFragment>,
+    pub count: i32,
+}
+
+impl LogEntry {
+    /// The message without its colours, including the repeat count.
+    pub fn plain_text(&self) -> String {
+        let mut text: String = self.fragments.iter().map(|f| f.text.as_str()).collect();
+        if self.count > 1 {
+            text += &format!(" x{}", self.count);
+        }
+        text
+    }
+
+    fn same_message(&self, category: LogCategory, fragments: &[LogFragment]) -> bool {
+        self.category == category
+            && self.fragments.len() == fragments.len()
+            && self
+                .fragments
+                .iter()
+                .zip(fragments.iter())
+                .all(|(a, b)| a.text == b.text)
+    }
+}
+
 pub struct GameLog {
-    pub entries: Vec<String>,
+    entries: VecDeque<LogEntry>,
+    /// The turn new messages are stamped with; kept up to date by the main loop.
+    pub turn: i32,
+    max_entries: usize,
+    /// Keep each new message as plain text too, for speech to pick up.
+    pub announce: bool,
+    new_messages: Vec<String>,
+}
+
+impl Default for GameLog {
+    fn default() -> GameLog {
+        GameLog {
+            entries: VecDeque::new(),
+            turn: 0,
+            max_entries: MAX_LOG_ENTRIES,
+            announce: false,
+            new_messages: Vec::new(),
+        }
+    }
+}
+
+impl GameLog {
+    /// Messages logged since the last call, when announcing.
+    pub fn take_new(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.new_messages)
+    }
+
+    /// Changes how much history is kept, forgetting the oldest entries if there are too many.
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = usize::max(1, max_entries);
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn add(&mut self, message: LogMessage) {
+        if self.announce {
+            self.new_messages
+                .push(message.fragments.iter().map(|f| f.text.as_str()).collect());
+        }
+        if let Some(last) = self.entries.back_mut() {
+            if last.same_message(message.category, &message.fragments) {
+                last.count += 1;
+                last.turn = self.turn;
+                return;
+            }
+        }
+        self.entries.push_back(LogEntry {
+            category: message.category,
+            turn: self.turn,
+            fragments: message.fragments,
+            count: 1,
+        });
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Logs a message in a single colour.
+    pub fn push<S: ToString>(&mut self, category: LogCategory, text: S) {
+        self.add(LogMessage::new(category).text(text));
+    }
+
+    /// Every entry still remembered, oldest first.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> + ExactSizeIterator {
+        self.entries.iter()
+    }
 }
@@ -1,28 +1,43 @@
+use super::container;
 use super::gamelog;
+use super::keybindings::{self, Action, KeyBindings, ACTIONS};
+use super::morgue;
+use super::palette::{Colour, Palette};
+use super::tileset;
 use super::{
-    CombatStats, Equipped, Hidden, HungerClock, HungerState, InBackpack, Map, Name, Player, Point,
-    Position, RunState, State, Viewshed,
+    map::TileType, spawner, spell_system, trade, AreaOfEffect, BurdenState, CombatStats, Confusion,
+    Consumable, Container, DefenseBonus, Encumbrance, EquipmentSlot, Equippable, Equipped, Hidden,
+    HungerClock, HungerState, InBackpack, InflictsDamage, Item, ItemValue, KnownSpells, Mana, Map,
+    MeleePowerBonus, Name, Player, Point, Position, ProvidesFood, ProvidesHealing, Purse, Ranged,
+    Renderable, State, Vendor, Viewshed,
 };
 use rltk::{Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
 
-fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+/// The names of everything visible at a map cell, as shown in the mouse tooltip.
+pub fn tooltip_names(ecs: &World, x: i32, y: i32) -> Vec<String> {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
     let hidden = ecs.read_storage::<Hidden>();
 
-    let mouse_pos = ctx.mouse_pos();
-    if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
-        return;
-    }
     let mut tooltip: Vec<String> = Vec::new();
+    if x < 0 || x >= map.width || y < 0 || y >= map.height {
+        return tooltip;
+    }
     for (name, position, _hidden) in (&names, &positions, !&hidden).join() {
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
+        if position.x == x && position.y == y && map.visible_tiles[idx] {
             tooltip.push(name.name.to_string());
         }
     }
+    tooltip
+}
+
+fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let palette = ecs.fetch::<Palette>();
+    let mouse_pos = ctx.mouse_pos();
+    let tooltip = tooltip_names(ecs, mouse_pos.0, mouse_pos.1);
 
     if !tooltip.is_empty() {
         let mut width: i32 = 0;
@@ -41,8 +56,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                 ctx.print_color(
                     left_x,
                     y,
-                    RGB::named(rltk::WHITE),
-                    RGB::named(rltk::GREY),
+                    palette.get(Colour::Text),
+                    palette.get(Colour::Muted),
                     s,
                 );
                 let padding = (width - s.len() as i32) - 1;
@@ -50,8 +65,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                     ctx.print_color(
                         arrow_pos.x - i,
                         y,
-                        RGB::named(rltk::WHITE),
-                        RGB::named(rltk::GREY),
+                        palette.get(Colour::Text),
+                        palette.get(Colour::Muted),
                         &" ".to_string(),
                     );
                 }
@@ -60,8 +75,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
             ctx.print_color(
                 arrow_pos.x,
                 arrow_pos.y,
-                RGB::named(rltk::WHITE),
-                RGB::named(rltk::GREY),
+                palette.get(Colour::Text),
+                palette.get(Colour::Muted),
                 &"->".to_string(),
             );
         } else {
@@ -72,8 +87,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                 ctx.print_color(
                     left_x + 1,
                     y,
-                    RGB::named(rltk::WHITE),
-                    RGB::named(rltk::GREY),
+                    palette.get(Colour::Text),
+                    palette.get(Colour::Muted),
                     s,
                 );
                 let padding = (width - s.len() as i32) - 1;
@@ -81,8 +96,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                     ctx.print_color(
                         arrow_pos.x + 1 + i,
                         y,
-                        RGB::named(rltk::WHITE),
-                        RGB::named(rltk::GREY),
+                        palette.get(Colour::Text),
+                        palette.get(Colour::Muted),
                         &" ".to_string(),
                     );
                 }
@@ -91,8 +106,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
             ctx.print_color(
                 arrow_pos.x,
                 arrow_pos.y,
-                RGB::named(rltk::WHITE),
-                RGB::named(rltk::GREY),
+                palette.get(Colour::Text),
+                palette.get(Colour::Muted),
                 &"<-".to_string(),
             );
         }
@@ -100,6 +115,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
 }
 
 pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let palette = gs.ecs.fetch::<Palette>();
+    let bindings = gs.ecs.fetch::<KeyBindings>();
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
     let backpack = gs.ecs.read_storage::<Equipped>();
@@ -116,22 +133,22 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
         y - 2,
         31,
         (count + 3) as i32,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
     );
     ctx.print_color(
         18,
         y - 2,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
         "Remove Which Item?",
     );
     ctx.print_color(
         18,
         y + count as i32 + 1,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!("{} to cancel", bindings.key_names(Action::Cancel)),
     );
 
     let mut equippable: Vec<Entity> = Vec::new();
@@ -143,22 +160,22 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
         ctx.set(
             17,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437('('),
         );
         ctx.set(
             18,
             y,
-            RGB::named(rltk::YELLOW),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Title),
+            palette.get(Colour::Background),
             97 + j as rltk::FontCharType,
         );
         ctx.set(
             19,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437(')'),
         );
 
@@ -171,7 +188,9 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => match key {
-            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => {
+                (ItemMenuResult::Cancel, None)
+            }
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if selection > -1 && selection < count as i32 {
@@ -186,36 +205,58 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
+pub fn burden_label(state: BurdenState) -> Option<&'static str> {
+    match state {
+        BurdenState::Unburdened => None,
+        BurdenState::Burdened => Some("Burdened"),
+        BurdenState::Overloaded => Some("Overloaded"),
+    }
+}
+
+fn burden_colour(palette: &Palette, state: BurdenState) -> RGB {
+    match state {
+        BurdenState::Unburdened => palette.get(Colour::Text),
+        BurdenState::Burdened => palette.get(Colour::Warning),
+        BurdenState::Overloaded => palette.get(Colour::Danger),
+    }
+}
+
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let palette = ecs.fetch::<Palette>();
     ctx.draw_box(
         0,
         43,
         79,
         6,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
     );
 
     let map = ecs.fetch::<Map>();
-    let depth = format!("Depth: {}", map.depth);
+    let depth = if map.depth == super::map_builder::TOWN_DEPTH {
+        "Town".to_string()
+    } else {
+        format!("Depth: {}", map.depth)
+    };
     ctx.print_color(
         2,
         43,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
         &depth,
     );
 
     let combat_stats = ecs.read_storage::<CombatStats>();
     let players = ecs.read_storage::<Player>();
     let hunger = ecs.read_storage::<HungerClock>();
-    for (_player, stats, hc) in (&players, &combat_stats, &hunger).join() {
+    let purses = ecs.read_storage::<Purse>();
+    for (_player, stats, hc, purse) in (&players, &combat_stats, &hunger, &purses).join() {
         let health = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
         ctx.print_color(
             12,
             43,
-            RGB::named(rltk::YELLOW),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Player),
+            palette.get(Colour::Background),
             &health,
         );
 
@@ -225,48 +266,88 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
             51,
             stats.hp,
             stats.max_hp,
-            RGB::named(rltk::RED),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Danger),
+            palette.get(Colour::Background),
+        );
+
+        if let Some(mana) = ecs.read_storage::<Mana>().get(*ecs.fetch::<Entity>()) {
+            let mp = format!(" MP: {} / {} ", mana.current, mana.max);
+            ctx.print_color(
+                12,
+                42,
+                palette.get(Colour::Highlight),
+                palette.get(Colour::Background),
+                &mp,
+            );
+            ctx.draw_bar_horizontal(
+                28,
+                42,
+                40,
+                mana.current,
+                mana.max,
+                palette.get(Colour::Mana),
+                palette.get(Colour::Background),
+            );
+        }
+
+        let gold = format!("Gold: {}", purse.gold);
+        ctx.print_color(
+            2,
+            42,
+            palette.get(Colour::Gold),
+            palette.get(Colour::Background),
+            &gold,
         );
 
         match hc.state {
             HungerState::WellFed => ctx.print_color(
                 71,
                 42,
-                RGB::named(rltk::GREEN),
-                RGB::named(rltk::BLACK),
+                palette.get(Colour::Good),
+                palette.get(Colour::Background),
                 "Well Fed",
             ),
             HungerState::Normal => {}
             HungerState::Hungry => ctx.print_color(
                 71,
                 42,
-                RGB::named(rltk::ORANGE),
-                RGB::named(rltk::BLACK),
+                palette.get(Colour::Warning),
+                palette.get(Colour::Background),
                 "Hungry",
             ),
             HungerState::Starving => ctx.print_color(
                 71,
                 42,
-                RGB::named(rltk::RED),
-                RGB::named(rltk::BLACK),
+                palette.get(Colour::Danger),
+                palette.get(Colour::Background),
                 "Starving",
             ),
         }
 
+        if let Some(load) = ecs
+            .read_storage::<Encumbrance>()
+            .get(*ecs.fetch::<Entity>())
+        {
+            if let Some(label) = burden_label(load.state) {
+                ctx.print_color(
+                    71,
+                    41,
+                    burden_colour(&palette, load.state),
+                    palette.get(Colour::Background),
+                    label,
+                );
+            }
+        }
+
         let log = ecs.fetch::<gamelog::GameLog>();
 
-        let mut y = 44;
-        for s in log.entries.iter().rev() {
-            if y < 49 {
-                ctx.print(2, y, s);
-            }
-            y += 1;
+        for (y, entry) in (44..49).zip(log.entries().rev()) {
+            draw_log_entry(ctx, &palette, 2, y, entry);
         }
     }
     // Draw mouse cursor
     let mouse_pos = ctx.mouse_pos();
-    ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
+    ctx.set_bg(mouse_pos.0, mouse_pos.1, palette.get(Colour::Selected));
 
     draw_tooltips(ecs, ctx);
 }
@@ -282,182 +363,504 @@ pub enum ItemMenuResult {
 pub enum MainMenuSelection {
     NewGame,
     LoadGame,
+    HighScores,
+    Achievements,
+    Options,
     Quit,
 }
 
+impl MainMenuSelection {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MainMenuSelection::NewGame => "Begin New Game",
+            MainMenuSelection::LoadGame => "Load Game",
+            MainMenuSelection::HighScores => "High Scores",
+            MainMenuSelection::Achievements => "Achievements",
+            MainMenuSelection::Options => "Options",
+            MainMenuSelection::Quit => "Quit",
+        }
+    }
+}
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum MainMenuResult {
     NoSelection { selected: MainMenuSelection },
     Selected { selected: MainMenuSelection },
 }
 
-pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
-    let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
-    let entities = gs.ecs.entities();
+/// What sort of thing an item is, for grouping the inventory.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum ItemCategory {
+    Weapon,
+    Armour,
+    Potion,
+    Scroll,
+    Food,
+    Other,
+}
 
-    let inventory = (&backpack, &names)
+impl ItemCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemCategory::Weapon => "Weapons",
+            ItemCategory::Armour => "Armour",
+            ItemCategory::Potion => "Potions",
+            ItemCategory::Scroll => "Scrolls",
+            ItemCategory::Food => "Food",
+            ItemCategory::Other => "Other",
+        }
+    }
+}
+
+pub fn item_category(ecs: &World, item: Entity) -> ItemCategory {
+    if let Some(equippable) = ecs.read_storage::<Equippable>().get(item) {
+        return match equippable.slot {
+            EquipmentSlot::Melee => ItemCategory::Weapon,
+            EquipmentSlot::Shield => ItemCategory::Armour,
+        };
+    }
+    if ecs.read_storage::<ProvidesHealing>().contains(item) {
+        ItemCategory::Potion
+    } else if ecs.read_storage::<ProvidesFood>().contains(item) {
+        ItemCategory::Food
+    } else if ecs.read_storage::<Consumable>().contains(item) {
+        ItemCategory::Scroll
+    } else {
+        ItemCategory::Other
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum InventorySort {
+    Category,
+    Name,
+    Value,
+}
+
+impl InventorySort {
+    pub fn label(&self) -> &'static str {
+        match self {
+            InventorySort::Category => "category",
+            InventorySort::Name => "name",
+            InventorySort::Value => "value",
+        }
+    }
+
+    pub fn next(self) -> InventorySort {
+        match self {
+            InventorySort::Category => InventorySort::Name,
+            InventorySort::Name => InventorySort::Value,
+            InventorySort::Value => InventorySort::Category,
+        }
+    }
+}
+
+/// Identical carried items (by name), shown as one line with a count.
+pub struct ItemStack {
+    pub name: String,
+    pub category: ItemCategory,
+    pub value: i32,
+    pub items: Vec<Entity>,
+}
+
+impl ItemStack {
+    pub fn label(&self) -> String {
+        if self.items.len() > 1 {
+            format!("{} (x{})", self.name, self.items.len())
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// How many stacks an item menu shows at once; letters run a to t on every page.
+pub const STACKS_PER_PAGE: usize = 20;
+
+/// Everything in `owner`'s backpack, stacked and sorted.
+pub fn carried_stacks(ecs: &World, owner: Entity, sort: InventorySort) -> Vec<ItemStack> {
+    let items = (&ecs.entities(), &ecs.read_storage::<InBackpack>())
         .join()
-        .filter(|item| item.0.owner == *player_entity);
-    let count = inventory.count();
+        .filter(|(_, pack)| pack.owner == owner)
+        .map(|(entity, _)| entity)
+        .collect();
+    stack_items(ecs, items, sort)
+}
 
-    let mut y = (25 - (count / 2)) as i32;
+/// The items lying on a map cell, stacked.
+pub fn stacks_at(ecs: &World, x: i32, y: i32) -> Vec<ItemStack> {
+    let items = (
+        &ecs.entities(),
+        &ecs.read_storage::<Item>(),
+        &ecs.read_storage::<Position>(),
+    )
+        .join()
+        .filter(|(_, _, pos)| pos.x == x && pos.y == y)
+        .map(|(entity, _, _)| entity)
+        .collect();
+    stack_items(ecs, items, InventorySort::Category)
+}
+
+/// Groups items into stacks of the same name, and sorts them. Containers each get their own
+/// line, since what's inside them differs.
+pub fn stack_items(ecs: &World, items: Vec<Entity>, sort: InventorySort) -> Vec<ItemStack> {
+    let names = ecs.read_storage::<Name>();
+    let values = ecs.read_storage::<ItemValue>();
+    let containers = ecs.read_storage::<Container>();
+
+    let mut stacks: Vec<ItemStack> = Vec::new();
+    for (entity, name) in items
+        .into_iter()
+        .filter_map(|entity| names.get(entity).map(|name| (entity, name)))
+    {
+        let stack = if containers.contains(entity) {
+            None
+        } else {
+            stacks
+                .iter_mut()
+                .find(|stack| stack.name == name.name && !containers.contains(stack.items[0]))
+        };
+        match stack {
+            Some(stack) => stack.items.push(entity),
+            None => stacks.push(ItemStack {
+                name: name.name.clone(),
+                category: item_category(ecs, entity),
+                value: values.get(entity).map_or(0, |v| v.gold),
+                items: vec![entity],
+            }),
+        }
+    }
+    match sort {
+        InventorySort::Category => stacks.sort_by(|a, b| {
+            a.category
+                .cmp(&b.category)
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        InventorySort::Name => stacks.sort_by(|a, b| a.name.cmp(&b.name)),
+        InventorySort::Value => {
+            stacks.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.name.cmp(&b.name)))
+        }
+    }
+    stacks
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum StackMenuResult {
+    NoResponse,
+    Cancel,
+    Page(usize),
+    Tab,
+    /// The first item of the stack picked, and how many are in it.
+    Selected {
+        item: Entity,
+        count: i32,
+    },
+}
+
+/// Stacks of items as a lettered list, a page at a time, with what Tab does in the footer.
+/// With `headings`, stacks sorted by category get a heading over each group.
+fn item_stack_menu(
+    ecs: &World,
+    ctx: &mut Rltk,
+    title: &str,
+    stacks: &[ItemStack],
+    page: usize,
+    headings: bool,
+    tab: Option<&str>,
+) -> StackMenuResult {
+    let palette = ecs.fetch::<Palette>();
+    let bindings = ecs.fetch::<KeyBindings>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let pages = usize::max(1, stacks.len().div_ceil(STACKS_PER_PAGE));
+    let page = usize::min(page, pages - 1);
+    let shown = &stacks[usize::min(page * STACKS_PER_PAGE, stacks.len())
+        ..usize::min((page + 1) * STACKS_PER_PAGE, stacks.len())];
+
+    let mut heading_rows = 0;
+    let mut last_category = None;
+    for stack in shown.iter() {
+        if headings && last_category != Some(stack.category) {
+            heading_rows += 1;
+            last_category = Some(stack.category);
+        }
+    }
+    let rows = (shown.len() + heading_rows) as i32;
+
+    let mut y = 25 - rows / 2;
     ctx.draw_box(
         15,
         y - 2,
-        31,
-        (count + 3) as i32,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
+        40,
+        rows + 4,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
     );
     ctx.print_color(
         18,
         y - 2,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "Inventory",
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        title,
     );
+    if let Some(load) = ecs.read_storage::<Encumbrance>().get(player_entity) {
+        let weight = format!("{} / {} lbs", load.carried, load.capacity);
+        ctx.print_color(
+            53 - weight.len() as i32,
+            y - 2,
+            burden_colour(&ecs.fetch::<Palette>(), load.state),
+            palette.get(Colour::Background),
+            weight,
+        );
+    }
     ctx.print_color(
         18,
-        y + count as i32 + 1,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        y + rows + 1,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        match tab {
+            Some(tab) => format!("TAB: {}  Page {}/{}", tab, page + 1, pages),
+            None => format!("Page {}/{}", page + 1, pages),
+        },
+    );
+    ctx.print_color(
+        18,
+        y + rows + 2,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!("{} to cancel", bindings.key_names(Action::Cancel)),
     );
 
-    let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
-        .join()
-        .filter(|item| item.1.owner == *player_entity)
-    {
+    let mut last_category = None;
+    for (j, stack) in shown.iter().enumerate() {
+        if headings && last_category != Some(stack.category) {
+            ctx.print_color(
+                17,
+                y,
+                palette.get(Colour::Muted),
+                palette.get(Colour::Background),
+                stack.category.label(),
+            );
+            last_category = Some(stack.category);
+            y += 1;
+        }
         ctx.set(
             17,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437('('),
         );
         ctx.set(
             18,
             y,
-            RGB::named(rltk::YELLOW),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Title),
+            palette.get(Colour::Background),
             97 + j as rltk::FontCharType,
         );
         ctx.set(
             19,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
+        ctx.print(21, y, stack.label());
         y += 1;
-        j += 1;
     }
 
     match ctx.key {
-        None => (ItemMenuResult::NoResponse, None),
+        None => StackMenuResult::NoResponse,
         Some(key) => match key {
-            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => StackMenuResult::Cancel,
+            VirtualKeyCode::Tab => StackMenuResult::Tab,
+            VirtualKeyCode::PageDown | VirtualKeyCode::Right if page + 1 < pages => {
+                StackMenuResult::Page(page + 1)
+            }
+            VirtualKeyCode::PageUp | VirtualKeyCode::Left if page > 0 => {
+                StackMenuResult::Page(page - 1)
+            }
             _ => {
                 let selection = rltk::letter_to_option(key);
-                if selection > -1 && selection < count as i32 {
-                    return (
-                        ItemMenuResult::Selected,
-                        Some(equippable[selection as usize]),
-                    );
+                if selection > -1 && selection < shown.len() as i32 {
+                    let stack = &shown[selection as usize];
+                    return StackMenuResult::Selected {
+                        item: stack.items[0],
+                        count: stack.items.len() as i32,
+                    };
                 }
-                (ItemMenuResult::NoResponse, None)
+                StackMenuResult::NoResponse
             }
         },
     }
 }
 
-pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+pub fn show_inventory(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    page: usize,
+    sort: InventorySort,
+) -> StackMenuResult {
+    let stacks = carried_stacks(&gs.ecs, *gs.ecs.fetch::<Entity>(), sort);
+    let tab = format!("by {}", sort.label());
+    item_stack_menu(
+        &gs.ecs,
+        ctx,
+        "Inventory",
+        &stacks,
+        page,
+        sort == InventorySort::Category,
+        Some(&tab),
+    )
+}
+
+/// Picks which stack to pick up, when several lie together.
+pub fn pickup_menu(ecs: &World, ctx: &mut Rltk, page: usize) -> StackMenuResult {
+    let player_pos = *ecs.fetch::<Point>();
+    let stacks = stacks_at(ecs, player_pos.x, player_pos.y);
+    item_stack_menu(ecs, ctx, "Pick Up Which Item?", &stacks, page, true, None)
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ContainerMode {
+    Take,
+    Put,
+}
+
+/// What a container menu lists: its contents to take out, or the backpack's to put in.
+/// Containers can't go inside each other.
+pub fn container_stacks(ecs: &World, container: Entity, mode: ContainerMode) -> Vec<ItemStack> {
+    match mode {
+        ContainerMode::Take => stack_items(
+            ecs,
+            container::contents(ecs, container),
+            InventorySort::Category,
+        ),
+        ContainerMode::Put => {
+            let containers = ecs.read_storage::<Container>();
+            carried_stacks(ecs, *ecs.fetch::<Entity>(), InventorySort::Category)
+                .into_iter()
+                .filter(|stack| !containers.contains(stack.items[0]))
+                .collect()
+        }
+    }
+}
+
+/// A chest or bag's contents; Tab switches between taking things out and putting them in.
+pub fn container_menu(
+    ecs: &World,
+    ctx: &mut Rltk,
+    container: Entity,
+    mode: ContainerMode,
+    page: usize,
+) -> StackMenuResult {
+    let name = ecs
+        .read_storage::<Name>()
+        .get(container)
+        .map_or("Container".to_string(), |n| n.name.clone());
+    let stacks = container_stacks(ecs, container, mode);
+    let (title, tab) = match mode {
+        ContainerMode::Take => (format!("Take From {}", name), "put items in"),
+        ContainerMode::Put => (format!("Put In {}", name), "take items out"),
+    };
+    item_stack_menu(ecs, ctx, &title, &stacks, page, true, Some(tab))
+}
+
+/// Lists the player's known spells with their mana cost; returns the index into
+/// `spell_system::SPELLS` of the one picked. Spells the player can't afford can't be picked.
+pub fn show_spells(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<usize>) {
+    let palette = gs.ecs.fetch::<Palette>();
+    let bindings = gs.ecs.fetch::<KeyBindings>();
     let player_entity = gs.ecs.fetch::<Entity>();
-    let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
-    let entities = gs.ecs.entities();
+    let known_spells = gs.ecs.read_storage::<KnownSpells>();
+    let mana = gs.ecs.read_storage::<Mana>();
+    let current_mana = mana.get(*player_entity).map_or(0, |m| m.current);
 
-    let inventory = (&backpack, &names)
-        .join()
-        .filter(|item| item.0.owner == *player_entity);
-    let count = inventory.count();
+    let spells: Vec<usize> = match known_spells.get(*player_entity) {
+        Some(known) => known
+            .spells
+            .iter()
+            .filter_map(|name| spell_system::spell_index(name))
+            .collect(),
+        None => Vec::new(),
+    };
+    let count = spells.len();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let y = (25 - (count / 2)) as i32;
     ctx.draw_box(
         15,
         y - 2,
         31,
         (count + 3) as i32,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
     );
     ctx.print_color(
         18,
         y - 2,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "Drop Which Item?",
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        "Cast Which Spell?",
     );
     ctx.print_color(
         18,
         y + count as i32 + 1,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!("{} to cancel", bindings.key_names(Action::Cancel)),
     );
 
-    let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
-        .join()
-        .filter(|item| item.1.owner == *player_entity)
-    {
+    for (j, index) in spells.iter().enumerate() {
+        let y = y + j as i32;
+        let spell = &spell_system::SPELLS[*index];
+        let fg = if spell.mana_cost <= current_mana {
+            palette.get(Colour::Text)
+        } else {
+            palette.get(Colour::Muted)
+        };
         ctx.set(
             17,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437('('),
         );
         ctx.set(
             18,
             y,
-            RGB::named(rltk::YELLOW),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Title),
+            palette.get(Colour::Background),
             97 + j as rltk::FontCharType,
         );
         ctx.set(
             19,
             y,
-            RGB::named(rltk::WHITE),
-            RGB::named(rltk::BLACK),
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
             rltk::to_cp437(')'),
         );
-
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
-        y += 1;
-        j += 1;
+        ctx.print_color(21, y, fg, palette.get(Colour::Background), spell.name);
+        ctx.print_color(
+            41,
+            y,
+            palette.get(Colour::Highlight),
+            palette.get(Colour::Background),
+            format!("{} MP", spell.mana_cost),
+        );
     }
 
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => match key {
-            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => {
+                (ItemMenuResult::Cancel, None)
+            }
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if selection > -1 && selection < count as i32 {
-                    return (
-                        ItemMenuResult::Selected,
-                        Some(equippable[selection as usize]),
-                    );
+                    let index = spells[selection as usize];
+                    if spell_system::SPELLS[index].mana_cost <= current_mana {
+                        return (ItemMenuResult::Selected, Some(index));
+                    }
                 }
                 (ItemMenuResult::NoResponse, None)
             }
@@ -465,6 +868,290 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     }
 }
 
+pub fn drop_item_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    page: usize,
+    sort: InventorySort,
+) -> StackMenuResult {
+    let stacks = carried_stacks(&gs.ecs, *gs.ecs.fetch::<Entity>(), sort);
+    let tab = format!("by {}", sort.label());
+    item_stack_menu(
+        &gs.ecs,
+        ctx,
+        "Drop Which Item?",
+        &stacks,
+        page,
+        sort == InventorySort::Category,
+        Some(&tab),
+    )
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum DropCountResult {
+    NoResponse,
+    Cancel,
+    Changed(i32),
+    Drop(i32),
+}
+
+/// Asks how many of a stack to drop. Arrows change the amount, digits type it, and Enter drops.
+pub fn drop_count_menu(ecs: &World, ctx: &mut Rltk, item: Entity, count: i32) -> DropCountResult {
+    let palette = ecs.fetch::<Palette>();
+    let bindings = ecs.fetch::<KeyBindings>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = ecs
+        .read_storage::<Name>()
+        .get(item)
+        .map_or("item".to_string(), |n| n.name.clone());
+    let max = carried_stacks(ecs, player_entity, InventorySort::Name)
+        .iter()
+        .find(|stack| stack.name == name)
+        .map_or(0, |stack| stack.items.len() as i32);
+    if max == 0 {
+        return DropCountResult::Cancel;
+    }
+
+    ctx.draw_box(
+        15,
+        21,
+        40,
+        5,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
+    );
+    ctx.print_color(
+        18,
+        21,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        "Drop How Many?",
+    );
+    ctx.print(17, 23, format!("{} of {} {}", count, max, name));
+    ctx.print_color(
+        18,
+        26,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!(
+            "{} to drop, {} to cancel",
+            bindings.key_names(Action::Confirm),
+            bindings.key_names(Action::Cancel)
+        ),
+    );
+
+    match ctx.key {
+        None => DropCountResult::NoResponse,
+        Some(key) => match key {
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => DropCountResult::Cancel,
+            _ if bindings.menu_action_for(key) == Some(Action::Confirm) => {
+                DropCountResult::Drop(count.clamp(1, max))
+            }
+            VirtualKeyCode::Up | VirtualKeyCode::Right => {
+                DropCountResult::Changed(i32::min(count + 1, max))
+            }
+            VirtualKeyCode::Down | VirtualKeyCode::Left => {
+                DropCountResult::Changed(i32::max(count - 1, 1))
+            }
+            VirtualKeyCode::Home => DropCountResult::Changed(1),
+            VirtualKeyCode::End => DropCountResult::Changed(max),
+            VirtualKeyCode::Back => DropCountResult::Changed(count / 10),
+            _ => match digit(key) {
+                // Typing appends a digit, restarting once the number would pass the stack size
+                Some(digit) => {
+                    let typed = count * 10 + digit;
+                    DropCountResult::Changed(if typed <= max { typed } else { digit })
+                }
+                None => DropCountResult::NoResponse,
+            },
+        },
+    }
+}
+
+fn digit(key: VirtualKeyCode) -> Option<i32> {
+    use VirtualKeyCode::*;
+    let digits = [
+        (Key0, Numpad0),
+        (Key1, Numpad1),
+        (Key2, Numpad2),
+        (Key3, Numpad3),
+        (Key4, Numpad4),
+        (Key5, Numpad5),
+        (Key6, Numpad6),
+        (Key7, Numpad7),
+        (Key8, Numpad8),
+        (Key9, Numpad9),
+    ];
+    digits
+        .iter()
+        .position(|(row, pad)| key == *row || key == *pad)
+        .map(|n| n as i32)
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum VendorMode {
+    Buy,
+    Sell,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VendorResult {
+    NoResponse,
+    Cancel,
+    Sell,
+    BuyMode,
+    SellMode,
+    Buy,
+}
+
+/// Draws one line of a vendor list: "(a) Name ....... price".
+fn vendor_line(ctx: &mut Rltk, palette: &Palette, y: i32, j: usize, name: &str, price: i32) {
+    ctx.set(
+        17,
+        y,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
+        rltk::to_cp437('('),
+    );
+    ctx.set(
+        18,
+        y,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        97 + j as rltk::FontCharType,
+    );
+    ctx.set(
+        19,
+        y,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
+        rltk::to_cp437(')'),
+    );
+    ctx.print(21, y, name);
+    ctx.print_color(
+        58,
+        y,
+        palette.get(Colour::Gold),
+        palette.get(Colour::Background),
+        format!("{} gp", price),
+    );
+}
+
+/// The trade screen. In buy mode the selection is the name of a ware; in sell mode it is the
+/// backpack item to sell.
+pub fn show_vendor_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    vendor: Entity,
+    mode: VendorMode,
+) -> (VendorResult, Option<Entity>, Option<String>) {
+    let palette = gs.ecs.fetch::<Palette>();
+    let bindings = gs.ecs.fetch::<KeyBindings>();
+    let vendors = gs.ecs.read_storage::<Vendor>();
+    let names = gs.ecs.read_storage::<Name>();
+    let player_entity = gs.ecs.fetch::<Entity>();
+
+    let vendor_name = names
+        .get(vendor)
+        .map(|n| n.name.clone())
+        .unwrap_or_else(|| "Vendor".to_string());
+    let (title, hint) = match mode {
+        VendorMode::Buy => (
+            format!("Buy from the {}", vendor_name),
+            format!(
+                "SPACE to sell, {} to leave",
+                bindings.key_names(Action::Cancel)
+            ),
+        ),
+        VendorMode::Sell => (
+            format!("Sell to the {}", vendor_name),
+            format!(
+                "SPACE to buy, {} to leave",
+                bindings.key_names(Action::Cancel)
+            ),
+        ),
+    };
+
+    let mut wares: Vec<(Option<Entity>, String, i32)> = Vec::new();
+    match mode {
+        VendorMode::Buy => {
+            if let Some(vendor) = vendors.get(vendor) {
+                for ware in spawner::vendor_wares(vendor.kind) {
+                    wares.push((
+                        None,
+                        ware.to_string(),
+                        spawner::item_value(ware).unwrap_or(0),
+                    ));
+                }
+            }
+        }
+        VendorMode::Sell => {
+            let backpack = gs.ecs.read_storage::<InBackpack>();
+            let values = gs.ecs.read_storage::<ItemValue>();
+            let entities = gs.ecs.entities();
+            for (entity, _pack, name, value) in (&entities, &backpack, &names, &values)
+                .join()
+                .filter(|item| item.1.owner == *player_entity)
+            {
+                wares.push((Some(entity), name.name.clone(), trade::sell_price(value)));
+            }
+        }
+    }
+    let count = wares.len();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        51,
+        (count + 3) as i32,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        &title,
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        hint,
+    );
+
+    for (j, ware) in wares.iter().enumerate() {
+        vendor_line(ctx, &palette, y + j as i32, j, &ware.1, ware.2);
+    }
+
+    match ctx.key {
+        None => (VendorResult::NoResponse, None, None),
+        Some(key) => match key {
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => {
+                (VendorResult::Cancel, None, None)
+            }
+            VirtualKeyCode::Space => match mode {
+                VendorMode::Buy => (VendorResult::SellMode, None, None),
+                VendorMode::Sell => (VendorResult::BuyMode, None, None),
+            },
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    let ware = &wares[selection as usize];
+                    return match mode {
+                        VendorMode::Buy => (VendorResult::Buy, None, Some(ware.1.clone())),
+                        VendorMode::Sell => (VendorResult::Sell, ware.0, None),
+                    };
+                }
+                (VendorResult::NoResponse, None, None)
+            }
+        },
+    }
+}
+
 pub fn ranged_target(
     gs: &mut State,
     ctx: &mut Rltk,
@@ -474,13 +1161,14 @@ pub fn ranged_target(
     let player_entity = gs.ecs.fetch::<Entity>();
     let player_pos = gs.ecs.fetch::<Point>();
     let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    let palette = gs.ecs.fetch::<Palette>();
     //println!("Started ranged_target");
 
     ctx.print_color(
         5,
         0,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
         "Select Target:",
     );
 
@@ -492,7 +1180,7 @@ pub fn ranged_target(
         for idx in visible.visible_tiles.iter() {
             let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *idx);
             if distance <= range as f32 {
-                ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
+                ctx.set_bg(idx.x, idx.y, palette.get(Colour::Range));
                 available_cells.push(idx);
             }
         }
@@ -517,111 +1205,563 @@ pub fn ranged_target(
     }
 
     if valid_target {
-        ctx.set_bg(target_pos.x, target_pos.y, RGB::named(rltk::CYAN));
+        ctx.set_bg(target_pos.x, target_pos.y, palette.get(Colour::Highlight));
     } else {
-        ctx.set_bg(target_pos.x, target_pos.y, RGB::named(rltk::RED));
+        ctx.set_bg(target_pos.x, target_pos.y, palette.get(Colour::Danger));
+    }
+
+    let bindings = gs.ecs.fetch::<KeyBindings>();
+    if let Some((dx, dy)) = ctx.key.and_then(|key| bindings.direction_for(key)) {
+        target_pos.x += dx;
+        target_pos.y += dy;
+    }
+    if ctx.key.and_then(|key| bindings.menu_action_for(key)) == Some(Action::Confirm) {
+        if valid_target {
+            //println!("target_pos: {:?}", target_pos);
+            return (ItemMenuResult::Selected, Some(target_pos), Some(target_pos));
+        } else {
+            return (ItemMenuResult::Cancel, None, None);
+        }
+    }
+    //    println!("target_pos: {:?}", target_pos);
+    return (ItemMenuResult::NoResponse, None, Some(target_pos));
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult {
+    NoSelection,
+    /// The report was scrolled to a new first line.
+    Scroll(i32),
+    QuitToMenu,
+}
+
+/// Shows the morgue report for the run that just ended under a title, scrolling with the
+/// arrow keys and page up/down.
+fn run_report(
+    ecs: &World,
+    ctx: &mut Rltk,
+    title: &str,
+    colour: RGB,
+    scroll: i32,
+) -> GameOverResult {
+    let palette = ecs.fetch::<Palette>();
+    let morgue = ecs.fetch::<morgue::Morgue>();
+    const FIRST_ROW: i32 = 4;
+    const ROWS: i32 = 42;
+
+    tileset::clear_screen(ctx);
+    ctx.print_color_centered(1, colour, palette.get(Colour::Background), title);
+    for (i, line) in morgue
+        .lines
+        .iter()
+        .skip(scroll as usize)
+        .take(ROWS as usize)
+        .enumerate()
+    {
+        ctx.print(2, FIRST_ROW + i as i32, line);
     }
 
+    let saved = match &morgue.file {
+        Some(file) => format!("Saved to {}. ", file),
+        None => "Could not save the morgue file. ".to_string(),
+    };
+    ctx.print_color_centered(
+        48,
+        palette.get(Colour::Prompt),
+        palette.get(Colour::Background),
+        format!("{}Arrows scroll, ESCAPE returns to the menu.", saved),
+    );
+
+    let max_scroll = i32::max(0, morgue.lines.len() as i32 - ROWS);
+    let scroll_to = |offset: i32| GameOverResult::Scroll(offset.clamp(0, max_scroll));
     match ctx.key {
-        None => {}
+        None => GameOverResult::NoSelection,
         Some(key) => match key {
-            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x -= 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
-            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x += 1;
-                //println!("target: {:?}{:?}", key, target_pos);
+            VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8 => {
+                scroll_to(scroll - 1)
             }
-            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.y -= 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
-            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.y += 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
-            // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x += 1;
-                target_pos.y -= 1;
-                //println!("target: {:?}{:?}", key, target_pos);
+            VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2 => {
+                scroll_to(scroll + 1)
             }
+            VirtualKeyCode::PageUp => scroll_to(scroll - ROWS),
+            VirtualKeyCode::PageDown => scroll_to(scroll + ROWS),
+            VirtualKeyCode::Escape | VirtualKeyCode::Return => GameOverResult::QuitToMenu,
+            _ => GameOverResult::NoSelection,
+        },
+    }
+}
 
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x -= 1;
-                target_pos.y -= 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
+pub fn game_over(ecs: &World, ctx: &mut Rltk, scroll: i32) -> GameOverResult {
+    let palette = ecs.fetch::<Palette>();
+    run_report(
+        ecs,
+        ctx,
+        "Your journey has ended!",
+        palette.get(Colour::Title),
+        scroll,
+    )
+}
 
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x += 1;
-                target_pos.y += 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
+pub fn victory(ecs: &World, ctx: &mut Rltk, scroll: i32) -> GameOverResult {
+    let palette = ecs.fetch::<Palette>();
+    run_report(
+        ecs,
+        ctx,
+        "Victory! You escaped with the Amulet of the Ancients.",
+        palette.get(Colour::Gold),
+        scroll,
+    )
+}
 
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => {
-                //println!("target: {:?}{:?}", key, target_pos);
-                target_pos.x -= 1;
-                target_pos.y += 1;
-                //println!("target: {:?}{:?}", key, target_pos);
-            }
-            VirtualKeyCode::Return => {
-                if valid_target {
-                    //println!("target_pos: {:?}", target_pos);
-                    return (ItemMenuResult::Selected, Some(target_pos), Some(target_pos));
-                } else {
-                    return (ItemMenuResult::Cancel, None, None);
-                }
-            }
-            _ => {}
-        },
+/// Prints a log entry in its colours, followed by the repeat count if there is one.
+fn draw_log_entry(ctx: &mut Rltk, palette: &Palette, x: i32, y: i32, entry: &gamelog::LogEntry) {
+    let mut x = x;
+    for fragment in entry.fragments.iter() {
+        ctx.print_color(
+            x,
+            y,
+            palette.get(fragment.colour),
+            palette.get(Colour::Background),
+            &fragment.text,
+        );
+        x += fragment.text.chars().count() as i32;
+    }
+    if entry.count > 1 {
+        ctx.print_color(
+            x,
+            y,
+            palette.get(Colour::Muted),
+            palette.get(Colour::Background),
+            format!(" x{}", entry.count),
+        );
     }
-    //    println!("target_pos: {:?}", target_pos);
-    return (ItemMenuResult::NoResponse, None, Some(target_pos));
 }
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum GameOverResult {
-    NoSelection,
-    QuitToMenu,
+pub enum LogViewResult {
+    NoResponse,
+    Changed {
+        scroll: i32,
+        filter: Option<gamelog::LogCategory>,
+    },
+    Close,
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
+/// The whole message history. `scroll` counts lines back from the newest message, and Tab
+/// cycles through showing one category at a time.
+pub fn show_log(
+    ecs: &World,
+    ctx: &mut Rltk,
+    scroll: i32,
+    filter: Option<gamelog::LogCategory>,
+) -> LogViewResult {
+    use gamelog::LogCategory;
+    const FIRST_ROW: i32 = 3;
+    const ROWS: i32 = 44;
+
+    let log = ecs.fetch::<gamelog::GameLog>();
+    let palette = ecs.fetch::<Palette>();
+    let shown: Vec<&gamelog::LogEntry> = log
+        .entries()
+        .filter(|entry| filter.is_none() || filter == Some(entry.category))
+        .collect();
+
+    tileset::clear_screen(ctx);
     ctx.print_color_centered(
-        15,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
-        "Your journey has ended!",
+        1,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!(
+            "Message Log ({})",
+            filter.map_or("All", |category| category.label())
+        ),
     );
+
+    // The newest message sits on the bottom row; scrolling walks back through older ones
+    let end = shown.len().saturating_sub(scroll as usize);
+    let start = end.saturating_sub(ROWS as usize);
+    for (i, entry) in shown[start..end].iter().enumerate() {
+        let y = FIRST_ROW + i as i32;
+        ctx.print_color(
+            1,
+            y,
+            palette.get(Colour::Muted),
+            palette.get(Colour::Background),
+            format!("{:>5}", entry.turn),
+        );
+        draw_log_entry(ctx, &palette, 8, y, entry);
+    }
+
     ctx.print_color_centered(
-        17,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
-        "One day, we'll tell you all about how you did.",
+        48,
+        palette.get(Colour::Prompt),
+        palette.get(Colour::Background),
+        "Arrows/PgUp/PgDn scroll, TAB filters, ESCAPE returns to the game.",
     );
-    ctx.print_color_centered(
-        18,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
-        "That day, sadly, is not in this chapter..",
+
+    let max_scroll = i32::max(0, shown.len() as i32 - ROWS);
+    let scroll_to = |offset: i32| LogViewResult::Changed {
+        scroll: offset.clamp(0, max_scroll),
+        filter,
+    };
+    match ctx.key {
+        None => LogViewResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8 => {
+                scroll_to(scroll + 1)
+            }
+            VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2 => {
+                scroll_to(scroll - 1)
+            }
+            VirtualKeyCode::PageUp => scroll_to(scroll + ROWS),
+            VirtualKeyCode::PageDown => scroll_to(scroll - ROWS),
+            VirtualKeyCode::Home => scroll_to(max_scroll),
+            VirtualKeyCode::End => scroll_to(0),
+            VirtualKeyCode::Tab => LogViewResult::Changed {
+                scroll: 0,
+                filter: match filter {
+                    None => Some(LogCategory::Combat),
+                    Some(LogCategory::Combat) => Some(LogCategory::Item),
+                    Some(LogCategory::Item) => Some(LogCategory::Trap),
+                    Some(LogCategory::Trap) => Some(LogCategory::System),
+                    Some(LogCategory::System) => None,
+                },
+            },
+            VirtualKeyCode::Escape | VirtualKeyCode::M => LogViewResult::Close,
+            _ => LogViewResult::NoResponse,
+        },
+    }
+}
+
+/// How hurt a creature looks, without giving away exact numbers.
+fn health_description(stats: &CombatStats) -> &'static str {
+    let percent = stats.hp * 100 / i32::max(1, stats.max_hp);
+    match percent {
+        p if p >= 100 => "Unhurt",
+        p if p >= 75 => "Lightly wounded",
+        p if p >= 50 => "Wounded",
+        p if p >= 25 => "Badly wounded",
+        _ => "Almost dead",
+    }
+}
+
+/// Breaks text into lines no wider than `width`, at spaces.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+const LOOK_PANEL_WIDTH: i32 = 32;
+
+/// Everything worth knowing about one entity, as coloured lines for the look panel.
+fn describe_entity(ecs: &World, entity: Entity) -> Vec<(RGB, String)> {
+    let palette = ecs.fetch::<Palette>();
+    let player_entity = ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let items = ecs.read_storage::<Item>();
+    let text = palette.get(Colour::Text);
+    let muted = palette.get(Colour::Muted);
+    let mut lines = Vec::new();
+
+    let name = match names.get(entity) {
+        Some(name) => name.name.clone(),
+        None => return lines,
+    };
+    let colour = renderables
+        .get(entity)
+        .map_or(text, |r| palette.entity_fg(ecs, entity, r));
+    lines.push((colour, name.clone()));
+    if let Some(text) = spawner::description(&name) {
+        for line in wrap_text(text, LOOK_PANEL_WIDTH as usize - 4) {
+            lines.push((muted, line));
+        }
+    }
+
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(entity) {
+        if entity == *player_entity {
+            lines.push((text, format!("HP: {} / {}", stats.hp, stats.max_hp)));
+        } else {
+            lines.push((
+                palette.get(Colour::Warning),
+                health_description(stats).to_string(),
+            ));
+        }
+    }
+    let is_item = items.get(entity).is_some();
+    if let Some(confusion) = ecs.read_storage::<Confusion>().get(entity) {
+        if is_item {
+            lines.push((text, format!("Confuses for {} turns", confusion.turns)));
+        } else {
+            lines.push((palette.get(Colour::Magic), "Confused".to_string()));
+        }
+    }
+
+    if let Some(equippable) = ecs.read_storage::<Equippable>().get(entity) {
+        let slot = match equippable.slot {
+            EquipmentSlot::Melee => "Weapon",
+            EquipmentSlot::Shield => "Shield",
+        };
+        lines.push((text, format!("Equipment: {}", slot)));
+    }
+    if let Some(bonus) = ecs.read_storage::<MeleePowerBonus>().get(entity) {
+        lines.push((text, format!("Power: +{}", bonus.power)));
+    }
+    if let Some(bonus) = ecs.read_storage::<DefenseBonus>().get(entity) {
+        lines.push((text, format!("Defense: +{}", bonus.defense)));
+    }
+    if let Some(healing) = ecs.read_storage::<ProvidesHealing>().get(entity) {
+        lines.push((text, format!("Heals {} hp", healing.heal_amount)));
+    }
+    if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(entity) {
+        lines.push((text, format!("Deals {} damage", damage.damage)));
+    }
+    if let Some(area) = ecs.read_storage::<AreaOfEffect>().get(entity) {
+        lines.push((text, format!("Blast radius {}", area.radius)));
+    }
+    if let Some(ranged) = ecs.read_storage::<Ranged>().get(entity) {
+        lines.push((text, format!("Range {}", ranged.range)));
+    }
+    if let Some(value) = ecs.read_storage::<ItemValue>().get(entity) {
+        lines.push((
+            palette.get(Colour::Gold),
+            format!("Worth {} gold", value.gold),
+        ));
+    }
+
+    lines
+}
+
+/// Lines describing whatever is at `target`: the tile itself, then anything visible there.
+pub fn describe_tile(ecs: &World, target: Point) -> Vec<(RGB, String)> {
+    let palette = ecs.fetch::<Palette>();
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let entities = ecs.entities();
+    let idx = map.xy_idx(target.x, target.y);
+
+    if !map.revealed_tiles[idx] {
+        return vec![(
+            palette.get(Colour::Muted),
+            "You haven't seen this place.".to_string(),
+        )];
+    }
+    let tile = match map.tiles[idx] {
+        TileType::Wall => "A wall",
+        TileType::Floor => "Floor",
+        TileType::DownStairs => "Stairs leading down",
+    };
+    let mut lines = vec![(palette.get(Colour::Title), tile.to_string())];
+    if !map.visible_tiles[idx] {
+        lines.push((
+            palette.get(Colour::Muted),
+            "You can't see there right now.".to_string(),
+        ));
+        return lines;
+    }
+
+    for (entity, pos, _hidden) in (&entities, &positions, !&hidden).join() {
+        if pos.x == target.x && pos.y == target.y {
+            let description = describe_entity(ecs, entity);
+            if !description.is_empty() {
+                lines.push((palette.get(Colour::Background), String::new()));
+                lines.extend(description);
+            }
+        }
+    }
+    lines
+}
+
+/// The next visible thing after `from`, in reading order, for hopping between them with Tab.
+fn next_visible_entity(ecs: &World, from: Point) -> Option<Point> {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let names = ecs.read_storage::<Name>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let player_pos = ecs.fetch::<Point>();
+
+    let mut spots: Vec<usize> = (&positions, &names, !&hidden)
+        .join()
+        .map(|(pos, _, _)| map.xy_idx(pos.x, pos.y))
+        .filter(|idx| map.visible_tiles[*idx])
+        .filter(|idx| *idx != map.xy_idx(player_pos.x, player_pos.y))
+        .collect();
+    spots.sort_unstable();
+    spots.dedup();
+
+    let current = map.xy_idx(from.x, from.y);
+    spots
+        .iter()
+        .find(|idx| **idx > current)
+        .or_else(|| spots.first())
+        .map(|idx| Point::new(*idx as i32 % map.width, *idx as i32 / map.width))
+}
+
+/// Moves a cursor around the map with the movement keys and describes whatever it is over.
+/// Tab jumps to the next visible creature or item, Return travels to the cursor and Escape
+/// leaves.
+pub fn look_mode(ecs: &World, ctx: &mut Rltk, cursor: Point) -> (ItemMenuResult, Point) {
+    let palette = ecs.fetch::<Palette>();
+    let bindings = ecs.fetch::<KeyBindings>();
+    let (width, height) = {
+        let map = ecs.fetch::<Map>();
+        (map.width, map.height)
+    };
+
+    ctx.print_color(
+        5,
+        0,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!(
+            "Look: move the cursor, TAB for next, {} to travel, {} to finish",
+            bindings.key_names(Action::Confirm),
+            bindings.key_names(Action::Cancel)
+        ),
     );
-    ctx.print_color_centered(
-        20,
-        RGB::named(rltk::MAGENTA),
-        RGB::named(rltk::BLACK),
-        "Press any key to return to the menu.",
+    ctx.set_bg(
+        cursor.x,
+        cursor.y,
+        ecs.fetch::<Palette>().get(Colour::Highlight),
+    );
+
+    // Keep the panel on the other half of the screen from the cursor
+    let lines = describe_tile(ecs, cursor);
+    let panel_x = if cursor.x > 40 {
+        1
+    } else {
+        79 - LOOK_PANEL_WIDTH
+    };
+    ctx.draw_box(
+        panel_x,
+        2,
+        LOOK_PANEL_WIDTH,
+        lines.len() as i32 + 1,
+        palette.get(Colour::Text),
+        palette.get(Colour::Background),
     );
+    for (i, (colour, line)) in lines.iter().enumerate() {
+        ctx.print_color(
+            panel_x + 2,
+            3 + i as i32,
+            *colour,
+            palette.get(Colour::Background),
+            line,
+        );
+    }
 
+    let mut cursor = cursor;
+    if let Some((dx, dy)) = ctx.key.and_then(|key| bindings.direction_for(key)) {
+        cursor.x += dx;
+        cursor.y += dy;
+    }
     match ctx.key {
-        None => GameOverResult::NoSelection,
-        Some(_) => GameOverResult::QuitToMenu,
+        None => {}
+        Some(key) => match key {
+            VirtualKeyCode::Tab => {
+                if let Some(next) = next_visible_entity(ecs, cursor) {
+                    cursor = next;
+                }
+            }
+            _ if bindings.menu_action_for(key) == Some(Action::Confirm) => {
+                return (ItemMenuResult::Selected, cursor)
+            }
+            _ if bindings.menu_action_for(key) == Some(Action::Cancel) => {
+                return (ItemMenuResult::Cancel, cursor)
+            }
+            _ if bindings.action_for(key) == Some(Action::Look) => {
+                return (ItemMenuResult::Cancel, cursor)
+            }
+            _ => {}
+        },
     }
+    cursor.x = cursor.x.clamp(0, width - 1);
+    cursor.y = cursor.y.clamp(0, height - 1);
+    (ItemMenuResult::NoResponse, cursor)
+}
+
+/// Lists every action with the keys currently bound to it, and any problems with the bindings.
+/// Returns true once the player is done reading.
+pub fn show_help(ecs: &World, ctx: &mut Rltk) -> bool {
+    let palette = ecs.fetch::<Palette>();
+    let bindings = ecs.fetch::<KeyBindings>();
+    tileset::clear_screen(ctx);
+    ctx.print_color_centered(
+        1,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!("Controls ({})", bindings.preset.label()),
+    );
+
+    let mut y = 3;
+    for action in ACTIONS.iter() {
+        let keys: Vec<String> = bindings
+            .keys_for(*action)
+            .into_iter()
+            .map(keybindings::key_label)
+            .collect();
+        ctx.print_color(
+            4,
+            y,
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
+            action.label(),
+        );
+        ctx.print_color(
+            30,
+            y,
+            palette.get(Colour::Highlight),
+            palette.get(Colour::Background),
+            if keys.is_empty() {
+                "(unbound)".to_string()
+            } else {
+                keys.join(", ")
+            },
+        );
+        y += 1;
+    }
+    ctx.print_color(
+        4,
+        y + 1,
+        palette.get(Colour::Muted),
+        palette.get(Colour::Background),
+        "Click a known tile to travel there. Keys can be changed in keybindings.json.",
+    );
+
+    y += 3;
+    for problem in bindings.problems.iter() {
+        ctx.print_color(
+            4,
+            y,
+            palette.get(Colour::Warning),
+            palette.get(Colour::Background),
+            problem,
+        );
+        y += 1;
+    }
+
+    ctx.print_color_centered(
+        48,
+        palette.get(Colour::Prompt),
+        palette.get(Colour::Background),
+        "Press ESCAPE to return to the game.",
+    );
+    matches!(
+        ctx.key,
+        Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::Return)
+    )
 }
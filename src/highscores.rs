@@ -0,0 +1,102 @@
+use super::{morgue::RunStatistics, Player, Purse};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HIGHSCORE_FILE: &str = "./highscores.json";
+
+/// Points for bringing the amulet home, on top of everything else.
+const VICTORY_BONUS: i32 = 5000;
+
+/// One finished run.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScore {
+    pub name: String,
+    pub score: i32,
+    pub depth: i32,
+    pub kills: i32,
+    pub xp: i32,
+    pub gold: i32,
+    pub won: bool,
+    pub seed: u64,
+    /// YYYY-MM-DD, in UTC.
+    pub date: String,
+}
+
+pub fn seconds_since_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Formats a Unix timestamp as a calendar date, without pulling in a date crate.
+fn date_from_timestamp(seconds: u64) -> String {
+    // Days to civil date, from Howard Hinnant's date algorithms
+    let z = (seconds / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn today() -> String {
+    date_from_timestamp(seconds_since_epoch())
+}
+
+/// Who is playing: the login name, as there is no character naming.
+fn player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "Adventurer".to_string())
+}
+
+/// Every recorded run, best first. A missing or unreadable file is an empty table.
+pub fn load_scores() -> Vec<HighScore> {
+    fs::read_to_string(HIGHSCORE_FILE)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Adds the run that just ended to the high-score file.
+pub fn record_run(ecs: &World, won: bool) {
+    let stats = ecs.fetch::<RunStatistics>();
+    let players = ecs.read_storage::<Player>();
+    let purses = ecs.read_storage::<Purse>();
+    let gold = (&players, &purses)
+        .join()
+        .map(|(_, purse)| purse.gold)
+        .next()
+        .unwrap_or(0);
+
+    let mut score = stats.deepest_depth * 100 + stats.xp + gold;
+    if won {
+        score += VICTORY_BONUS;
+    }
+
+    let mut scores = load_scores();
+    scores.push(HighScore {
+        name: player_name(),
+        score,
+        depth: stats.deepest_depth,
+        kills: stats.kills.values().sum(),
+        xp: stats.xp,
+        gold,
+        won,
+        seed: stats.seed,
+        date: today(),
+    });
+    scores.sort_by_key(|s| std::cmp::Reverse(s.score));
+
+    if let Ok(data) = serde_json::to_string_pretty(&scores) {
+        if fs::write(HIGHSCORE_FILE, data).is_err() {
+            rltk::console::log("Unable to write the high-score file");
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use super::{gamelog::GameLog, HungerClock, HungerState, RunState, SufferDamage};
+use super::{
+    gamelog::{GameLog, LogCategory},
+    morgue::RunStatistics,
+    HungerClock, HungerState, RunState, SufferDamage,
+};
 use specs::prelude::*;
 
 pub struct HungerSystem {}
@@ -12,11 +16,19 @@ impl<'a> System<'a> for HungerSystem {
         ReadExpect<'a, RunState>,
         WriteStorage<'a, SufferDamage>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RunStatistics>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut hunger_clock, player_entity, runstate, mut inflict_damage, mut log) =
-            data;
+        let (
+            entities,
+            mut hunger_clock,
+            player_entity,
+            runstate,
+            mut inflict_damage,
+            mut log,
+            mut run_stats,
+        ) = data;
 
         for (entity, mut clock) in (&entities, &mut hunger_clock).join() {
             let mut proceed = false;
@@ -43,29 +55,32 @@ impl<'a> System<'a> for HungerSystem {
                             clock.state = HungerState::Normal;
                             clock.duration = 200;
                             if entity == *player_entity {
-                                log.entries.push("You are no longer well fed.".to_string());
+                                log.push(LogCategory::System, "You are no longer well fed.");
                             }
                         }
                         HungerState::Normal => {
                             clock.state = HungerState::Hungry;
                             clock.duration = 200;
                             if entity == *player_entity {
-                                log.entries.push("You are hungry.".to_string());
+                                log.push(LogCategory::System, "You are hungry.");
                             }
                         }
                         HungerState::Hungry => {
                             clock.state = HungerState::Starving;
                             clock.duration = 200;
                             if entity == *player_entity {
-                                log.entries.push("You are starving!".to_string());
+                                log.push(LogCategory::System, "You are starving!");
                             }
                         }
                         HungerState::Starving => {
                             // Inflict damage from hunger
                             if entity == *player_entity {
-                                log.entries.push("Your hunger pangs are getting painful! You suffer 1 hp damage.".to_string());
+                                log.push(LogCategory::System, "Your hunger pangs are getting painful! You suffer 1 hp damage.");
+                            }
+                            SufferDamage::new_damage(&mut inflict_damage, entity, 1, false);
+                            if entity == *player_entity {
+                                run_stats.last_hurt_by = Some("starvation".to_string());
                             }
-                            SufferDamage::new_damage(&mut inflict_damage, entity, 1);
                         }
                     }
                 }
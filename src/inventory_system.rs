@@ -1,8 +1,15 @@
 use super::{
-    gamelog::GameLog, map::Map, particle_system::ParticleBuilder, AreaOfEffect, CombatStats,
-    Confusion, Consumable, Equippable, Equipped, HungerClock, HungerState, InBackpack,
-    InflictsDamage, MagicMapper, Name, Position, ProvidesFood, ProvidesHealing, RunState,
-    SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
+    encumbrance_system,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    map::Map,
+    morgue::{self, RunStatistics},
+    palette::Colour,
+    particle_system::ParticleBuilder,
+    AreaOfEffect, CombatStats, Confusion, Consumable, Encumbrance, Equippable, Equipped, Gold,
+    HungerClock, HungerState, InBackpack, InContainer, InflictsDamage, ItemWeight, KnownSpells,
+    MagicMapper, Name, Position, ProvidesFood, ProvidesHealing, Purse, RunState, SufferDamage,
+    TeachesSpell, TownPortal, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem,
+    WantsToUseItem,
 };
 use specs::prelude::*;
 
@@ -17,28 +24,114 @@ impl<'a> System<'a> for ItemCollectionSystem {
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        Entities<'a>,
+        ReadStorage<'a, Gold>,
+        WriteStorage<'a, Purse>,
+        WriteExpect<'a, RunStatistics>,
+        ReadStorage<'a, ItemWeight>,
+        WriteStorage<'a, Encumbrance>,
+        ReadStorage<'a, InContainer>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut gamelog,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            entities,
+            gold,
+            mut purses,
+            mut run_stats,
+            weights,
+            mut encumbrance,
+            in_container,
+        ) = data;
 
         for pickup in wants_pickup.join() {
-            positions.remove(pickup.item);
-            backpack
-                .insert(
-                    pickup.item,
-                    InBackpack {
-                        owner: pickup.collected_by,
-                    },
-                )
-                .expect("Unable to insert backpack entry");
-
-            if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!(
-                    "You pick up the {}.",
-                    names.get(pickup.item).unwrap().name
-                ));
+            let name = names.get(pickup.item).unwrap().name.clone();
+            let mut picking = vec![pickup.item];
+            if let Some(pos) = positions.get(pickup.item) {
+                picking.extend(
+                    (&entities, &names, &positions)
+                        .join()
+                        .filter(|(item, item_name, item_pos)| {
+                            *item != pickup.item
+                                && item_name.name == name
+                                && item_pos.x == pos.x
+                                && item_pos.y == pos.y
+                        })
+                        .map(|(item, _, _)| item)
+                        .take(i32::max(0, pickup.count - 1) as usize)
+                        .collect::<Vec<Entity>>(),
+                );
+            }
+
+            let mut picked_up = 0;
+            for item in picking {
+                // Refuse anything that would take the load past its limit. A bag weighs as
+                // much as what's in it, too.
+                let pounds = weights.get(item).map_or(0, |w| w.pounds)
+                    + (&in_container, &weights)
+                        .join()
+                        .filter(|(inside, _)| inside.container == item)
+                        .map(|(_, weight)| weight.pounds)
+                        .sum::<i32>();
+                if let Some(load) = encumbrance.get_mut(pickup.collected_by) {
+                    if load.carried + pounds > encumbrance_system::weight_limit(load.capacity) {
+                        if pickup.collected_by == *player_entity {
+                            gamelog.add(
+                                LogMessage::new(LogCategory::Item)
+                                    .text("The ")
+                                    .item(&name)
+                                    .text(" is too heavy to carry with everything else."),
+                            );
+                        }
+                        break;
+                    }
+                    load.carried += pounds;
+                }
+
+                positions.remove(item);
+
+                // Gold goes straight into the purse rather than the backpack
+                if let Some(gold) = gold.get(item) {
+                    if let Some(purse) = purses.get_mut(pickup.collected_by) {
+                        purse.gold += gold.amount;
+                    }
+                    if pickup.collected_by == *player_entity {
+                        gamelog.push(
+                            LogCategory::Item,
+                            format!("You pick up {} gold.", gold.amount),
+                        );
+                    }
+                    entities.delete(item).expect("Unable to delete gold");
+                    continue;
+                }
+
+                backpack
+                    .insert(
+                        item,
+                        InBackpack {
+                            owner: pickup.collected_by,
+                        },
+                    )
+                    .expect("Unable to insert backpack entry");
+                if pickup.collected_by == *player_entity {
+                    morgue::tally(&mut run_stats.items_found, &name);
+                }
+                picked_up += 1;
+            }
+
+            if picked_up > 0 && pickup.collected_by == *player_entity {
+                let message = LogMessage::new(LogCategory::Item).text("You pick up the ");
+                gamelog.add(if picked_up > 1 {
+                    message.item(&name).text(format!(" (x{}).", picked_up))
+                } else {
+                    message.item(&name).text(".")
+                });
             }
         }
 
@@ -73,6 +166,10 @@ impl<'a> System<'a> for ItemUseSystem {
         WriteStorage<'a, HungerClock>,
         ReadStorage<'a, MagicMapper>,
         WriteExpect<'a, RunState>,
+        ReadStorage<'a, TownPortal>,
+        ReadStorage<'a, TeachesSpell>,
+        WriteStorage<'a, KnownSpells>,
+        WriteExpect<'a, RunStatistics>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -99,6 +196,10 @@ impl<'a> System<'a> for ItemUseSystem {
             mut hunger_clocks,
             magic_mapper,
             mut runstate,
+            town_portal,
+            teaches_spell,
+            mut known_spells,
+            mut run_stats,
         ) = data;
         for (entity, useitem) in (&entities, &wants_use).join() {
             // Targeting
@@ -106,7 +207,7 @@ impl<'a> System<'a> for ItemUseSystem {
             let mut used_item = false;
             match useitem.target {
                 None => {
-                    targets.push(*player_entity);
+                    targets.push(entity);
                 }
                 Some(target) => {
                     let area_effect = aoe.get(useitem.item);
@@ -142,13 +243,48 @@ impl<'a> System<'a> for ItemUseSystem {
                 None => {}
                 Some(_) => {
                     used_item = true;
-                    gamelog
-                        .entries
-                        .push("The map is revealed to you!".to_string());
+                    gamelog.push(LogCategory::Item, "The map is revealed to you!");
                     *runstate = RunState::MagicMapReveal { row: 0 };
                 }
             }
 
+            // Town portals take the user back to the surface
+            if town_portal.get(useitem.item).is_some() {
+                if map.depth == super::map_builder::TOWN_DEPTH {
+                    gamelog.push(
+                        LogCategory::Item,
+                        "You are already in town; the scroll crumbles uselessly.",
+                    );
+                } else {
+                    *runstate = RunState::TownPortal;
+                }
+            }
+
+            // Spellbooks teach their spell to whoever reads them
+            if let Some(teaches) = teaches_spell.get(useitem.item) {
+                if let Some(known) = known_spells.get_mut(entity) {
+                    if entity == *player_entity {
+                        if known.spells.contains(&teaches.spell) {
+                            gamelog.push(
+                                LogCategory::Item,
+                                format!(
+                                    "You already know {}; the book crumbles to dust.",
+                                    teaches.spell
+                                ),
+                            );
+                        } else {
+                            gamelog.push(
+                                LogCategory::Item,
+                                format!("You learn to cast {}!", teaches.spell),
+                            );
+                        }
+                    }
+                    if !known.spells.contains(&teaches.spell) {
+                        known.spells.push(teaches.spell.clone());
+                    }
+                }
+            }
+
             // It it is edible, eat it!
             let item_edible = provides_food.get(useitem.item);
             match item_edible {
@@ -161,10 +297,12 @@ impl<'a> System<'a> for ItemUseSystem {
                     if let Some(hc) = hc {
                         hc.state = HungerState::WellFed;
                         hc.duration = 20;
-                        gamelog.entries.push(format!(
-                            "You eat the {}.",
-                            names.get(useitem.item).unwrap().name
-                        ));
+                        gamelog.add(
+                            LogMessage::new(LogCategory::Item)
+                                .text("You eat the ")
+                                .item(&names.get(useitem.item).unwrap().name)
+                                .text("."),
+                        );
                     }
                 }
             }
@@ -186,7 +324,12 @@ impl<'a> System<'a> for ItemUseSystem {
                         {
                             to_unequip.push(item_entity);
                             if target == *player_entity {
-                                gamelog.entries.push(format!("You unequip {}.", name.name));
+                                gamelog.add(
+                                    LogMessage::new(LogCategory::Item)
+                                        .text("You unequip ")
+                                        .item(&name.name)
+                                        .text("."),
+                                );
                             }
                         }
                     }
@@ -209,10 +352,12 @@ impl<'a> System<'a> for ItemUseSystem {
                         .expect("Unable to insert equipped component");
                     backpack.remove(useitem.item);
                     if target == *player_entity {
-                        gamelog.entries.push(format!(
-                            "You equip {}.",
-                            names.get(useitem.item).unwrap().name
-                        ));
+                        gamelog.add(
+                            LogMessage::new(LogCategory::Item)
+                                .text("You equip ")
+                                .item(&names.get(useitem.item).unwrap().name)
+                                .text("."),
+                        );
                     }
                 }
             }
@@ -222,17 +367,22 @@ impl<'a> System<'a> for ItemUseSystem {
             match item_heals {
                 None => {}
                 Some(healer) => {
+                    // Spell effects heal too, but only things carried count as potions
+                    if entity == *player_entity && backpack.get(useitem.item).is_some() {
+                        run_stats.potions_drunk += 1;
+                    }
                     for target in targets.iter() {
                         let stats = combat_stats.get_mut(*target);
                         if let Some(stats) = stats {
                             //let mut used_item = false;
                             stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
                             if entity == *player_entity {
-                                gamelog.entries.push(format!(
-                                    "You use the {}, healing {} hp.",
-                                    names.get(useitem.item).unwrap().name,
-                                    healer.heal_amount
-                                ));
+                                gamelog.add(
+                                    LogMessage::new(LogCategory::Item)
+                                        .text("You use the ")
+                                        .item(&names.get(useitem.item).unwrap().name)
+                                        .text(format!(", healing {} hp.", healer.heal_amount)),
+                                );
                             }
 
                             let pos = positions.get(*target);
@@ -240,7 +390,7 @@ impl<'a> System<'a> for ItemUseSystem {
                                 particle_builder.request(
                                     pos.x,
                                     pos.y,
-                                    rltk::RGB::named(rltk::GREEN),
+                                    Colour::Good,
                                     rltk::RGB::named(rltk::BLACK),
                                     rltk::to_cp437('♥'),
                                     200.0,
@@ -259,21 +409,39 @@ impl<'a> System<'a> for ItemUseSystem {
                 Some(damage) => {
                     //let mut used_item = false;
                     for mob in targets.iter() {
-                        SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
+                        SufferDamage::new_damage(
+                            &mut suffer_damage,
+                            *mob,
+                            damage.damage,
+                            entity == *player_entity,
+                        );
+                        if *mob == *player_entity {
+                            let item_name = &names.get(useitem.item).unwrap().name;
+                            run_stats.last_hurt_by = Some(if entity == *player_entity {
+                                format!("their own {}", item_name)
+                            } else {
+                                format!("{}'s {}", names.get(entity).unwrap().name, item_name)
+                            });
+                        }
                         if entity == *player_entity {
+                            run_stats.damage_dealt += damage.damage;
                             let mob_name = names.get(*mob).unwrap();
                             let item_name = names.get(useitem.item).unwrap();
-                            gamelog.entries.push(format!(
-                                "You use {} on {}, inflicting {} hp.",
-                                item_name.name, mob_name.name, damage.damage
-                            ));
+                            gamelog.add(
+                                LogMessage::new(LogCategory::Combat)
+                                    .text("You use ")
+                                    .item(&item_name.name)
+                                    .text(" on ")
+                                    .npc(&mob_name.name)
+                                    .text(format!(", inflicting {} hp.", damage.damage)),
+                            );
                         }
                         let pos = positions.get(*mob);
                         if let Some(pos) = pos {
                             particle_builder.request(
                                 pos.x,
                                 pos.y,
-                                rltk::RGB::named(rltk::RED),
+                                Colour::Danger,
                                 rltk::RGB::named(rltk::BLACK),
                                 rltk::to_cp437('‼'),
                                 200.0,
@@ -297,17 +465,21 @@ impl<'a> System<'a> for ItemUseSystem {
                             if entity == *player_entity {
                                 let mob_name = names.get(*mob).unwrap();
                                 let item_name = names.get(useitem.item).unwrap();
-                                gamelog.entries.push(format!(
-                                    "You use {} on {}, confusing them.",
-                                    item_name.name, mob_name.name
-                                ));
+                                gamelog.add(
+                                    LogMessage::new(LogCategory::Combat)
+                                        .text("You use ")
+                                        .item(&item_name.name)
+                                        .text(" on ")
+                                        .npc(&mob_name.name)
+                                        .text(", confusing them."),
+                                );
                             }
                             let pos = positions.get(*mob);
                             if let Some(pos) = pos {
                                 particle_builder.request(
                                     pos.x,
                                     pos.y,
-                                    rltk::RGB::named(rltk::MAGENTA),
+                                    Colour::Magic,
                                     rltk::RGB::named(rltk::BLACK),
                                     rltk::to_cp437('?'),
                                     200.0,
@@ -368,22 +540,37 @@ impl<'a> System<'a> for ItemDropSystem {
                 dropper_pos.x = dropped_pos.x;
                 dropper_pos.y = dropped_pos.y;
             }
-            positions
-                .insert(
-                    to_drop.item,
-                    Position {
-                        x: dropper_pos.x,
-                        y: dropper_pos.y,
-                    },
-                )
-                .expect("Unable to insert position");
-            backpack.remove(to_drop.item);
+            let name = names.get(to_drop.item).unwrap().name.clone();
+            let mut dropping = vec![to_drop.item];
+            dropping.extend(
+                (&entities, &backpack, &names)
+                    .join()
+                    .filter(|(item, pack, item_name)| {
+                        *item != to_drop.item && pack.owner == entity && item_name.name == name
+                    })
+                    .map(|(item, _, _)| item)
+                    .take(i32::max(0, to_drop.count - 1) as usize),
+            );
+            for item in dropping.iter() {
+                positions
+                    .insert(
+                        *item,
+                        Position {
+                            x: dropper_pos.x,
+                            y: dropper_pos.y,
+                        },
+                    )
+                    .expect("Unable to insert position");
+                backpack.remove(*item);
+            }
 
             if entity == *player_entity {
-                gamelog.entries.push(format!(
-                    "You drop the {}.",
-                    names.get(to_drop.item).unwrap().name
-                ));
+                let message = LogMessage::new(LogCategory::Item).text("You drop the ");
+                gamelog.add(if dropping.len() > 1 {
+                    message.item(&name).text(format!(" (x{}).", dropping.len()))
+                } else {
+                    message.item(&name).text(".")
+                });
             }
         }
 
@@ -0,0 +1,566 @@
+use rltk::VirtualKeyCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+const KEYBINDING_FILE: &str = "./keybindings.json";
+
+/// Everything the player can do with a single key: from the map, or in a menu.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveEast,
+    MoveWest,
+    MoveNorthEast,
+    MoveNorthWest,
+    MoveSouthEast,
+    MoveSouthWest,
+    Wait,
+    PickUp,
+    OpenInventory,
+    DropItem,
+    RemoveItem,
+    CastSpell,
+    Descend,
+    Look,
+    Autoexplore,
+    TravelToStairs,
+    MessageLog,
+    Help,
+    SaveAndQuit,
+    Confirm,
+    Cancel,
+}
+
+/// Every action, in the order the help screen lists them.
+pub const ACTIONS: &[Action] = &[
+    Action::MoveNorth,
+    Action::MoveSouth,
+    Action::MoveEast,
+    Action::MoveWest,
+    Action::MoveNorthEast,
+    Action::MoveNorthWest,
+    Action::MoveSouthEast,
+    Action::MoveSouthWest,
+    Action::Wait,
+    Action::PickUp,
+    Action::OpenInventory,
+    Action::DropItem,
+    Action::RemoveItem,
+    Action::CastSpell,
+    Action::Descend,
+    Action::Look,
+    Action::Autoexplore,
+    Action::TravelToStairs,
+    Action::MessageLog,
+    Action::Help,
+    Action::SaveAndQuit,
+    Action::Confirm,
+    Action::Cancel,
+];
+
+impl Action {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveNorth => "Move north",
+            Action::MoveSouth => "Move south",
+            Action::MoveEast => "Move east",
+            Action::MoveWest => "Move west",
+            Action::MoveNorthEast => "Move north-east",
+            Action::MoveNorthWest => "Move north-west",
+            Action::MoveSouthEast => "Move south-east",
+            Action::MoveSouthWest => "Move south-west",
+            Action::Wait => "Wait a turn",
+            Action::PickUp => "Pick up",
+            Action::OpenInventory => "Use an item",
+            Action::DropItem => "Drop an item",
+            Action::RemoveItem => "Remove equipment",
+            Action::CastSpell => "Cast a spell",
+            Action::Descend => "Descend / take portal",
+            Action::Look => "Look around",
+            Action::Autoexplore => "Autoexplore",
+            Action::TravelToStairs => "Travel to the stairs",
+            Action::MessageLog => "Message log",
+            Action::Help => "This help",
+            Action::SaveAndQuit => "Save and quit",
+            Action::Confirm => "Confirm (menus)",
+            Action::Cancel => "Cancel (menus)",
+        }
+    }
+
+    /// Menu actions only apply while a menu is open, so they can share keys with map actions.
+    pub fn in_menus(&self) -> bool {
+        matches!(self, Action::Confirm | Action::Cancel)
+    }
+
+    /// Which way a movement action goes, for moving the player or a cursor.
+    pub fn direction(&self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveNorth => Some((0, -1)),
+            Action::MoveSouth => Some((0, 1)),
+            Action::MoveEast => Some((1, 0)),
+            Action::MoveWest => Some((-1, 0)),
+            Action::MoveNorthEast => Some((1, -1)),
+            Action::MoveNorthWest => Some((-1, -1)),
+            Action::MoveSouthEast => Some((1, 1)),
+            Action::MoveSouthWest => Some((-1, 1)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Preset {
+    Vi,
+    Numpad,
+    Wasd,
+}
+
+impl Preset {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Preset::Vi => "Vi keys",
+            Preset::Numpad => "Numpad",
+            Preset::Wasd => "WASD",
+        }
+    }
+}
+
+/// What goes in the key binding file: a preset, plus any actions bound differently from it.
+/// Keys are written by name, e.g. `"G"`, `"Comma"` or `"Numpad5"`.
+#[derive(Serialize, Deserialize)]
+struct BindingConfig {
+    preset: Preset,
+    #[serde(default)]
+    overrides: BTreeMap<Action, Vec<String>>,
+}
+
+/// Keys that do the same thing whichever preset is chosen: the arrows and the numpad always move.
+fn common_bindings() -> Vec<(VirtualKeyCode, Action)> {
+    use Action::*;
+    use VirtualKeyCode as K;
+    vec![
+        (K::Up, MoveNorth),
+        (K::Down, MoveSouth),
+        (K::Right, MoveEast),
+        (K::Left, MoveWest),
+        (K::Numpad8, MoveNorth),
+        (K::Numpad2, MoveSouth),
+        (K::Numpad6, MoveEast),
+        (K::Numpad4, MoveWest),
+        (K::Numpad9, MoveNorthEast),
+        (K::Numpad7, MoveNorthWest),
+        (K::Numpad3, MoveSouthEast),
+        (K::Numpad1, MoveSouthWest),
+        (K::Numpad5, Wait),
+        (K::G, PickUp),
+        (K::I, OpenInventory),
+        (K::R, RemoveItem),
+        (K::Period, Descend),
+        (K::M, MessageLog),
+        (K::O, Autoexplore),
+        (K::T, TravelToStairs),
+        (K::Slash, Help),
+        (K::F1, Help),
+        (K::Escape, SaveAndQuit),
+        (K::Space, Wait),
+        (K::Return, Confirm),
+        (K::Escape, Cancel),
+    ]
+}
+
+fn preset_bindings(preset: Preset) -> Vec<(VirtualKeyCode, Action)> {
+    use Action::*;
+    use VirtualKeyCode as K;
+    let mut bindings = common_bindings();
+    bindings.extend(match preset {
+        Preset::Vi => vec![
+            (K::K, MoveNorth),
+            (K::J, MoveSouth),
+            (K::L, MoveEast),
+            (K::H, MoveWest),
+            (K::Y, MoveNorthEast),
+            (K::U, MoveNorthWest),
+            (K::N, MoveSouthEast),
+            (K::B, MoveSouthWest),
+            (K::D, DropItem),
+            (K::Z, CastSpell),
+            (K::X, Look),
+        ],
+        // Nothing but the numpad and arrows for moving, leaving the letters free
+        Preset::Numpad => vec![(K::D, DropItem), (K::Z, CastSpell), (K::X, Look)],
+        Preset::Wasd => vec![
+            (K::W, MoveNorth),
+            (K::S, MoveSouth),
+            (K::D, MoveEast),
+            (K::A, MoveWest),
+            (K::E, MoveNorthEast),
+            (K::Q, MoveNorthWest),
+            (K::C, MoveSouthEast),
+            (K::Z, MoveSouthWest),
+            (K::F, DropItem),
+            (K::V, CastSpell),
+            (K::X, Look),
+        ],
+    });
+    bindings
+}
+
+/// Which key does what. Loaded from the key binding file, falling back to the vi preset.
+pub struct KeyBindings {
+    pub preset: Preset,
+    bindings: Vec<(VirtualKeyCode, Action)>,
+    overrides: BTreeMap<Action, Vec<String>>,
+    /// Problems found while loading: unknown key names and keys bound to two actions.
+    pub problems: Vec<String>,
+}
+
+impl KeyBindings {
+    pub fn from_preset(preset: Preset) -> KeyBindings {
+        KeyBindings::build(preset, BTreeMap::new())
+    }
+
+    fn build(preset: Preset, overrides: BTreeMap<Action, Vec<String>>) -> KeyBindings {
+        let mut problems = Vec::new();
+        let mut bindings = preset_bindings(preset);
+        // Overridden actions lose their preset keys, and their new keys take priority
+        for (action, keys) in overrides.iter() {
+            bindings.retain(|(_, a)| a != action);
+            for name in keys.iter().rev() {
+                match key_from_name(name) {
+                    Some(key) => bindings.insert(0, (key, *action)),
+                    None => {
+                        problems.push(format!("Unknown key \"{}\" for {}", name, action.label()))
+                    }
+                }
+            }
+        }
+
+        for (i, (key, action)) in bindings.iter().enumerate() {
+            if action.in_menus() && is_letter(*key) {
+                problems.push(format!(
+                    "{} picks menu entries, so it can't be used for {}",
+                    key_label(*key),
+                    action.label()
+                ));
+            }
+            if let Some((_, first)) = bindings[..i]
+                .iter()
+                .find(|(k, a)| k == key && a != action && a.in_menus() == action.in_menus())
+            {
+                problems.push(format!(
+                    "{} is bound to both {} and {}; using {}",
+                    key_label(*key),
+                    first.label(),
+                    action.label(),
+                    first.label()
+                ));
+            }
+        }
+
+        KeyBindings {
+            preset,
+            bindings,
+            overrides,
+            problems,
+        }
+    }
+
+    pub fn load() -> KeyBindings {
+        match fs::read_to_string(KEYBINDING_FILE) {
+            Err(_) => KeyBindings::from_preset(Preset::Vi),
+            Ok(data) => match serde_json::from_str::<BindingConfig>(&data) {
+                Ok(config) => KeyBindings::build(config.preset, config.overrides),
+                Err(e) => {
+                    let mut bindings = KeyBindings::from_preset(Preset::Vi);
+                    bindings
+                        .problems
+                        .push(format!("Could not read {}: {}", KEYBINDING_FILE, e));
+                    bindings
+                }
+            },
+        }
+    }
+
+    pub fn save(&self) {
+        let config = BindingConfig {
+            preset: self.preset,
+            overrides: self.overrides.clone(),
+        };
+        if let Ok(data) = serde_json::to_string_pretty(&config) {
+            if fs::write(KEYBINDING_FILE, data).is_err() {
+                rltk::console::log("Unable to write the key binding file");
+            }
+        }
+    }
+
+    /// Switches to another preset, keeping any overrides.
+    pub fn set_preset(&mut self, preset: Preset) {
+        *self = KeyBindings::build(preset, std::mem::take(&mut self.overrides));
+    }
+
+    pub fn action_for(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, a)| *k == key && !a.in_menus())
+            .map(|(_, action)| *action)
+    }
+
+    /// What a key does in a menu. Letters are left to pick entries.
+    pub fn menu_action_for(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, a)| *k == key && a.in_menus() && !is_letter(*k))
+            .map(|(_, action)| *action)
+    }
+
+    /// The keys for an action, as a menu footer names them.
+    pub fn key_names(&self, action: Action) -> String {
+        self.keys_for(action)
+            .into_iter()
+            .map(|key| key_label(key).to_uppercase())
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
+    /// Which way a key moves a cursor, if it is bound to a movement action.
+    pub fn direction_for(&self, key: VirtualKeyCode) -> Option<(i32, i32)> {
+        self.action_for(key).and_then(|action| action.direction())
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<VirtualKeyCode> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+}
+
+fn is_letter(key: VirtualKeyCode) -> bool {
+    rltk::letter_to_option(key) >= 0
+}
+
+/// How a key is shown to the player.
+pub fn key_label(key: VirtualKeyCode) -> String {
+    use VirtualKeyCode as K;
+    match key {
+        K::Period => ".".to_string(),
+        K::Comma => ",".to_string(),
+        K::Slash => "/".to_string(),
+        K::Semicolon => ";".to_string(),
+        K::Key0 => "0".to_string(),
+        K::Key1 => "1".to_string(),
+        K::Key2 => "2".to_string(),
+        K::Key3 => "3".to_string(),
+        K::Key4 => "4".to_string(),
+        K::Key5 => "5".to_string(),
+        K::Key6 => "6".to_string(),
+        K::Key7 => "7".to_string(),
+        K::Key8 => "8".to_string(),
+        K::Key9 => "9".to_string(),
+        _ => format!("{:?}", key),
+    }
+}
+
+/// Every key that can be bound, by the name used in the key binding file.
+const BINDABLE_KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode as K;
+    &[
+        K::A,
+        K::B,
+        K::C,
+        K::D,
+        K::E,
+        K::F,
+        K::G,
+        K::H,
+        K::I,
+        K::J,
+        K::K,
+        K::L,
+        K::M,
+        K::N,
+        K::O,
+        K::P,
+        K::Q,
+        K::R,
+        K::S,
+        K::T,
+        K::U,
+        K::V,
+        K::W,
+        K::X,
+        K::Y,
+        K::Z,
+        K::Key0,
+        K::Key1,
+        K::Key2,
+        K::Key3,
+        K::Key4,
+        K::Key5,
+        K::Key6,
+        K::Key7,
+        K::Key8,
+        K::Key9,
+        K::Numpad0,
+        K::Numpad1,
+        K::Numpad2,
+        K::Numpad3,
+        K::Numpad4,
+        K::Numpad5,
+        K::Numpad6,
+        K::Numpad7,
+        K::Numpad8,
+        K::Numpad9,
+        K::Up,
+        K::Down,
+        K::Left,
+        K::Right,
+        K::Home,
+        K::End,
+        K::PageUp,
+        K::PageDown,
+        K::Insert,
+        K::Delete,
+        K::Escape,
+        K::Return,
+        K::Space,
+        K::Tab,
+        K::Back,
+        K::Period,
+        K::Comma,
+        K::Slash,
+        K::Semicolon,
+        K::Minus,
+        K::Equals,
+        K::F1,
+        K::F2,
+        K::F3,
+        K::F4,
+        K::F5,
+        K::F6,
+        K::F7,
+        K::F8,
+        K::F9,
+        K::F10,
+        K::F11,
+        K::F12,
+    ]
+};
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name) || key_label(**key) == name)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VirtualKeyCode as K;
+
+    const PRESETS: &[Preset] = &[Preset::Vi, Preset::Numpad, Preset::Wasd];
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for preset in PRESETS {
+            let bindings = KeyBindings::from_preset(*preset);
+            assert!(
+                bindings.problems.is_empty(),
+                "{}: {:?}",
+                preset.label(),
+                bindings.problems
+            );
+        }
+    }
+
+    #[test]
+    fn presets_bind_every_action() {
+        for preset in PRESETS {
+            let bindings = KeyBindings::from_preset(*preset);
+            for action in ACTIONS {
+                assert!(
+                    !bindings.keys_for(*action).is_empty(),
+                    "{}: nothing bound to {}",
+                    preset.label(),
+                    action.label()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn vi_diagonals_match_the_numpad() {
+        let bindings = KeyBindings::from_preset(Preset::Vi);
+        for (letter, numpad) in [
+            (K::Y, K::Numpad9),
+            (K::U, K::Numpad7),
+            (K::N, K::Numpad3),
+            (K::B, K::Numpad1),
+        ] {
+            assert_eq!(bindings.action_for(letter), bindings.action_for(numpad));
+        }
+        assert_eq!(bindings.direction_for(K::Y), Some((1, -1)));
+        assert_eq!(bindings.direction_for(K::U), Some((-1, -1)));
+    }
+
+    #[test]
+    fn cursor_follows_the_preset() {
+        let wasd = KeyBindings::from_preset(Preset::Wasd);
+        assert_eq!(wasd.direction_for(K::W), Some((0, -1)));
+        assert_eq!(wasd.direction_for(K::Q), Some((-1, -1)));
+        assert_eq!(wasd.direction_for(K::H), None);
+        assert_eq!(wasd.direction_for(K::G), None);
+
+        let numpad = KeyBindings::from_preset(Preset::Numpad);
+        assert_eq!(numpad.direction_for(K::Numpad6), Some((1, 0)));
+        assert_eq!(numpad.direction_for(K::K), None);
+    }
+
+    #[test]
+    fn overrides_report_conflicts_and_win() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(Action::Look, vec!["G".to_string()]);
+        let bindings = KeyBindings::build(Preset::Vi, overrides);
+        assert_eq!(bindings.action_for(K::G), Some(Action::Look));
+        assert_eq!(bindings.action_for(K::X), None);
+        assert_eq!(bindings.problems.len(), 1);
+        assert!(bindings.problems[0].contains("Pick up"));
+    }
+
+    #[test]
+    fn menu_keys_share_keys_with_the_map() {
+        let bindings = KeyBindings::from_preset(Preset::Vi);
+        assert_eq!(bindings.action_for(K::Escape), Some(Action::SaveAndQuit));
+        assert_eq!(bindings.menu_action_for(K::Escape), Some(Action::Cancel));
+        assert_eq!(bindings.menu_action_for(K::Return), Some(Action::Confirm));
+        assert_eq!(bindings.action_for(K::Return), None);
+    }
+
+    #[test]
+    fn menu_keys_leave_letters_alone() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(Action::Cancel, vec!["Q".to_string(), "Back".to_string()]);
+        let bindings = KeyBindings::build(Preset::Vi, overrides);
+        assert_eq!(bindings.menu_action_for(K::Q), None);
+        assert_eq!(bindings.menu_action_for(K::Back), Some(Action::Cancel));
+        assert_eq!(bindings.problems.len(), 1);
+        assert!(bindings.problems[0].contains("picks menu entries"));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(
+            Action::Look,
+            vec!["NoSuchKey".to_string(), "Comma".to_string()],
+        );
+        let bindings = KeyBindings::build(Preset::Vi, overrides);
+        assert_eq!(bindings.action_for(K::Comma), Some(Action::Look));
+        assert_eq!(bindings.problems.len(), 1);
+        assert!(bindings.problems[0].contains("NoSuchKey"));
+    }
+}
@@ -1,32 +1,58 @@
 extern crate serde;
 
+// rltk only takes one backend, and `opengl` is switched on by default
+#[cfg(all(feature = "opengl", feature = "terminal"))]
+compile_error!(
+    "The terminal build needs the OpenGL backend switched off: \
+     cargo build --no-default-features --features terminal"
+);
+
+mod achievements;
+mod auto_move;
+mod bystander_ai_system;
 mod components;
+mod container;
 mod damage_system;
+mod encumbrance_system;
 mod gamelog;
 mod gui;
+mod highscores;
 mod hunger_system;
 mod inventory_system;
+mod keybindings;
 mod map;
+mod map_builder;
 mod map_indexing_system;
 mod melee_combat_system;
 mod menu;
 mod monster_ai_system;
+mod monster_items;
+mod morgue;
+mod palette;
 mod particle_system;
 mod player;
+mod prefab_builder;
+mod prefabs;
 mod random_table;
 mod rect;
 mod rex_assets;
 mod saveload_system;
+mod settings;
 mod spawner;
+mod speech;
+mod spell_system;
+mod tileset;
+mod trade;
 mod trigger_system;
 mod visibility_system;
 
 use components::*;
+use gamelog::LogCategory;
 use inventory_system::ItemRemoveSystem;
 use map::{Map, MAPHEIGHT, MAPWIDTH};
 use monster_ai_system::MonsterAI;
 use player::Player;
-use random_table::RandomTable;
+use random_table::{RandomTable, Weight};
 use rex_assets::RexAssets;
 use rltk::{GameState, Point, Rltk};
 use specs::{
@@ -40,8 +66,26 @@ pub enum RunState {
     PreRun,
     PlayerTurn,
     MonsterTurn,
-    ShowInventory,
-    ShowDropItem,
+    ShowInventory {
+        page: usize,
+        sort: gui::InventorySort,
+    },
+    ShowDropItem {
+        page: usize,
+        sort: gui::InventorySort,
+    },
+    ShowDropCount {
+        item: Entity,
+        count: i32,
+    },
+    ShowPickup {
+        page: usize,
+    },
+    ShowContainer {
+        container: Entity,
+        mode: gui::ContainerMode,
+        page: usize,
+    },
     ShowTargeting {
         range: i32,
         item: Entity,
@@ -51,13 +95,45 @@ pub enum RunState {
     MainMenu {
         menu_selection: gui::MainMenuSelection,
     },
+    ShowHighScores,
+    ShowHelp,
+    ShowAchievements,
+    ShowOptions {
+        selected: usize,
+    },
+    ShowLog {
+        scroll: i32,
+        filter: Option<LogCategory>,
+    },
     SaveGame,
     NextLevel,
     ShowRemoveItem,
-    GameOver,
+    GameOver {
+        scroll: i32,
+    },
     MagicMapReveal {
         row: i32,
     },
+    ShowVendor {
+        vendor: Entity,
+        mode: gui::VendorMode,
+    },
+    TownPortal,
+    TakePortal {
+        depth: i32,
+    },
+    ShowSpells,
+    Victory {
+        scroll: i32,
+    },
+    LookMode {
+        cursor: Point,
+    },
+    ShowSpellTargeting {
+        range: i32,
+        spell: usize,
+        target: Option<Point>,
+    },
 }
 
 pub struct State {
@@ -68,8 +144,11 @@ impl State {
     fn run_systems(&mut self) {
         let mut vis = visibility_system::VisibilitySystem {};
         vis.run_now(&self.ecs);
+        monster_items::use_items(&mut self.ecs);
         let mut mob = MonsterAI {};
         mob.run_now(&self.ecs);
+        let mut bystanders = bystander_ai_system::BystanderAI {};
+        bystanders.run_now(&self.ecs);
         let mut triggers = trigger_system::TriggerSystem {};
         triggers.run_now(&self.ecs);
         let mut mapindex = map_indexing_system::MapIndexingSystem {};
@@ -78,20 +157,65 @@ impl State {
         melee.run_now(&self.ecs);
         let mut damage = damage_system::DamageSystem {};
         damage.run_now(&self.ecs);
+        monster_items::pick_up_items(&mut self.ecs);
         let mut pickup = inventory_system::ItemCollectionSystem {};
         pickup.run_now(&self.ecs);
+        spell_system::cast_spells(&mut self.ecs);
+        spell_system::summon_minions(&mut self.ecs);
         let mut potions = inventory_system::ItemUseSystem {};
         potions.run_now(&self.ecs);
         let mut drop_items = inventory_system::ItemDropSystem {};
         drop_items.run_now(&self.ecs);
         let mut item_remove = ItemRemoveSystem {};
         item_remove.run_now(&self.ecs);
+        let mut encumbrance = encumbrance_system::EncumbranceSystem {};
+        encumbrance.run_now(&self.ecs);
         let mut hunger = hunger_system::HungerSystem {};
         hunger.run_now(&self.ecs);
+        let mut mana = spell_system::ManaSystem {};
+        mana.run_now(&self.ecs);
         let mut particles = particle_system::ParticleSpawnSystem {};
         particles.run_now(&self.ecs);
 
         self.ecs.maintain();
+        achievements::check_achievements(&self.ecs);
+    }
+
+    fn generate_world_map(&mut self, new_depth: i32) {
+        let level = {
+            let mut rng = self.ecs.write_resource::<rltk::RandomNumberGenerator>();
+            map_builder::build_level(new_depth, &mut rng)
+        };
+        {
+            let mut stats = self.ecs.write_resource::<morgue::RunStatistics>();
+            stats.deepest_depth = i32::max(stats.deepest_depth, new_depth);
+        }
+        {
+            let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            *worldmap_resource = level.map.clone();
+        }
+
+        // Spawn bad guys
+        level.spawn_entities(&mut self.ecs);
+
+        // Place the player and update resources
+        let (player_x, player_y) = (level.starting_position.x, level.starting_position.y);
+        let mut player_position = self.ecs.write_resource::<Point>();
+        *player_position = Point::new(player_x, player_y);
+        let mut position_components = self.ecs.write_storage::<Position>();
+        let player_entity = self.ecs.fetch::<Entity>();
+        let player_pos_comp = position_components.get_mut(*player_entity);
+        if let Some(player_pos_comp) = player_pos_comp {
+            player_pos_comp.x = player_x;
+            player_pos_comp.y = player_y;
+        }
+
+        // Mark the player's visibility as dirty
+        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
+        let vs = viewshed_components.get_mut(*player_entity);
+        if let Some(vs) = vs {
+            vs.dirty = true;
+        }
     }
 
     fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
@@ -134,6 +258,24 @@ impl State {
         to_delete
     }
 
+    /// Seeds the random number generator for a new run and resets the run statistics.
+    fn start_run(&mut self) {
+        let seed = highscores::seconds_since_epoch();
+        self.ecs.insert(rltk::RandomNumberGenerator::seeded(seed));
+        self.ecs.insert(morgue::RunStatistics {
+            seed,
+            ..Default::default()
+        });
+    }
+
+    /// Everything that happens once when a run ends, whether in death or victory.
+    fn end_run(&mut self, won: bool) {
+        morgue::write_morgue(&mut self.ecs, won);
+        highscores::record_run(&self.ecs, won);
+        // An autosave of a finished run is no use to anyone
+        saveload_system::delete_save();
+    }
+
     fn game_over_cleanup(&mut self) {
         // Delete everything
         let mut to_delete = Vec::new();
@@ -144,42 +286,23 @@ impl State {
             self.ecs.delete_entity(*del).expect("Deletion failed");
         }
 
-        // Build a new map and place the player
-        let worldmap;
-        {
-            let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            *worldmap_resource = Map::new_map_rooms_and_corridors(1);
-            worldmap = worldmap_resource.clone();
-        }
+        // Start counting afresh, with a new seed
+        self.start_run();
 
-        // Spawn bad guys
-        for room in worldmap.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.ecs, room, 1);
-        }
-
-        // Place the player and update resources
-        let (player_x, player_y) = worldmap.rooms[0].center();
-        let player_entity = spawner::player(&mut self.ecs, player_x, player_y);
-        let mut player_position = self.ecs.write_resource::<Point>();
-        *player_position = Point::new(player_x, player_y);
-        let mut position_components = self.ecs.write_storage::<Position>();
-        let mut player_entity_writer = self.ecs.write_resource::<Entity>();
-        *player_entity_writer = player_entity;
-        let player_pos_comp = position_components.get_mut(player_entity);
-        if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = player_x;
-            player_pos_comp.y = player_y;
+        // Spawn a new player
+        {
+            let player_entity = spawner::player(&mut self.ecs, 0, 0);
+            let mut player_entity_writer = self.ecs.write_resource::<Entity>();
+            *player_entity_writer = player_entity;
         }
 
-        // Mark the player's visibility as dirty
-        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
-        let vs = viewshed_components.get_mut(player_entity);
-        if let Some(vs) = vs {
-            vs.dirty = true;
-        }
+        // Build a new map and place the player
+        self.generate_world_map(map_builder::TOWN_DEPTH);
     }
 
-    fn goto_next_level(&mut self) {
+    /// Replaces the current level with a new one at `new_depth`, keeping the player and
+    /// their belongings.
+    fn goto_level(&mut self, new_depth: i32) {
         // Delete entities that aren't the player or his/her equipment
         let to_delete = self.entities_to_remove_on_level_change();
         for target in to_delete {
@@ -189,76 +312,133 @@ impl State {
         }
 
         // Build a new map and place the player
-        let worldmap;
-        let mut current_depth: i32 = 1;
-        {
-            let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            let current_depth = worldmap_resource.depth;
-            *worldmap_resource = Map::new_map_rooms_and_corridors(current_depth + 1);
-            worldmap = worldmap_resource.clone();
-        }
-
-        // Spawn bad guys
-        for room in worldmap.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.ecs, room, current_depth + 1);
-        }
-
-        // Place the player and update resources
-        let (player_x, player_y) = worldmap.rooms[0].center();
-        let mut player_position = self.ecs.write_resource::<Point>();
-        *player_position = Point::new(player_x, player_y);
-        let mut position_components = self.ecs.write_storage::<Position>();
-        let player_entity = self.ecs.fetch::<Entity>();
-        let player_pos_comp = position_components.get_mut(*player_entity);
-        if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = player_x;
-            player_pos_comp.y = player_y;
-        }
+        self.generate_world_map(new_depth);
+    }
 
-        // Mark the player's visibility as dirty
-        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
-        let vs = viewshed_components.get_mut(*player_entity);
-        if let Some(vs) = vs {
-            vs.dirty = true;
-        }
+    fn goto_next_level(&mut self) {
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.goto_level(current_depth + 1);
 
         // Notify the player and give them some health
+        let player_entity = self.ecs.fetch::<Entity>();
         let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
-        gamelog
-            .entries
-            .push("You descend to the next level, and take a moment to heal.".to_string());
+        gamelog.push(
+            LogCategory::System,
+            "You descend to the next level, and take a moment to heal.",
+        );
+        if current_depth + 1 == map_builder::FINAL_DEPTH {
+            gamelog.push(
+                LogCategory::System,
+                "A deathly chill fills the air. The Lich and its amulet are near.",
+            );
+        }
         let mut player_health_store = self.ecs.write_storage::<CombatStats>();
         let player_health = player_health_store.get_mut(*player_entity);
         if let Some(player_health) = player_health {
             player_health.hp = i32::max(player_health.hp, player_health.max_hp / 2);
         }
     }
+
+    /// Returns to town, leaving a portal back to the depth the scroll was read at.
+    fn goto_town(&mut self) {
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.goto_level(map_builder::TOWN_DEPTH);
+
+        let (x, y) = {
+            let player_pos = self.ecs.fetch::<Point>();
+            (player_pos.x, player_pos.y)
+        };
+        spawner::portal(&mut self.ecs, x + 1, y, current_depth);
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog.push(
+            LogCategory::System,
+            format!(
+                "You are whisked back to town. A portal to depth {} shimmers beside you.",
+                current_depth
+            ),
+        );
+    }
+
+    /// Counts a consumable towards the items used this run. Equipping something isn't using it.
+    fn record_item_use(&self, item: Entity) {
+        if !self.ecs.read_storage::<Consumable>().contains(item) {
+            return;
+        }
+        if let Some(name) = self.ecs.read_storage::<Name>().get(item) {
+            morgue::tally(
+                &mut self
+                    .ecs
+                    .write_resource::<morgue::RunStatistics>()
+                    .items_used,
+                &name.name,
+            );
+        }
+    }
+
+    /// Drops `count` of the player's stack of `item`.
+    fn drop_items(&mut self, item: Entity, count: i32) {
+        self.ecs
+            .write_storage::<WantsToDropItem>()
+            .insert(*self.ecs.fetch::<Entity>(), WantsToDropItem { item, count })
+            .expect("Unable to insert intent");
+    }
+
+    fn overloaded(&self) -> bool {
+        self.ecs
+            .read_storage::<Encumbrance>()
+            .get(*self.ecs.fetch::<Entity>())
+            .is_some_and(|load| load.state == BurdenState::Overloaded)
+    }
+
+    /// A run is won by standing in town with the quest item in your pack.
+    fn won(&self) -> bool {
+        if self.ecs.fetch::<Map>().depth != map_builder::TOWN_DEPTH {
+            return false;
+        }
+        let player_entity = self.ecs.fetch::<Entity>();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let quest_items = self.ecs.read_storage::<QuestItem>();
+        (&backpack, &quest_items)
+            .join()
+            .any(|(pack, _)| pack.owner == *player_entity)
+    }
+
+    fn take_portal(&mut self, depth: i32) {
+        self.goto_level(depth);
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog.push(LogCategory::System, "You step through the portal.");
+    }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
-        ctx.cls();
+        tileset::clear_screen(ctx);
         particle_system::cull_dead_particles(&mut self.ecs, ctx);
-        map::draw_map(&self.ecs, ctx);
-        {
+        if self.ecs.has_value::<tileset::Tileset>() {
+            tileset::draw_world(&self.ecs, ctx);
+        } else {
+            map::draw_map(&self.ecs, ctx);
+            let entities = self.ecs.entities();
             let positions = self.ecs.read_storage::<Position>();
             let renderables = self.ecs.read_storage::<Renderable>();
             let hidden = self.ecs.read_storage::<Hidden>();
             let map = self.ecs.fetch::<Map>();
+            let palette = self.ecs.fetch::<palette::Palette>();
 
-            let mut data = (&positions, &renderables, !&hidden)
+            let mut data = (&entities, &positions, &renderables, !&hidden)
                 .join()
                 .collect::<Vec<_>>();
-            data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-            for (pos, render, _hidden) in data.iter() {
+            data.sort_by(|&a, &b| b.2.render_order.cmp(&a.2.render_order));
+            for (entity, pos, render, _hidden) in data.iter() {
                 let idx = map.xy_idx(pos.x, pos.y);
                 if map.visible_tiles[idx] {
-                    ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph)
+                    let fg = palette.entity_fg(&self.ecs, *entity, render);
+                    ctx.set(pos.x, pos.y, fg, render.bg, render.glyph)
                 }
             }
-
-            gui::draw_ui(&self.ecs, ctx);
         }
+        gui::draw_ui(&self.ecs, ctx);
 
         let mut newrunstate;
         {
@@ -283,27 +463,85 @@ impl GameState for State {
                             newrunstate = RunState::AwaitingInput;
                             saveload_system::delete_save();
                         }
+                        gui::MainMenuSelection::HighScores => {
+                            newrunstate = RunState::ShowHighScores
+                        }
+                        gui::MainMenuSelection::Achievements => {
+                            newrunstate = RunState::ShowAchievements
+                        }
+                        gui::MainMenuSelection::Options => {
+                            newrunstate = RunState::ShowOptions { selected: 0 }
+                        }
                         gui::MainMenuSelection::Quit => {
                             ::std::process::exit(0);
                         }
                     },
                 }
             }
+            RunState::ShowHighScores => {
+                if menu::high_scores(&self.ecs, ctx) {
+                    newrunstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::HighScores,
+                    };
+                }
+            }
+            RunState::ShowHelp => {
+                if gui::show_help(&self.ecs, ctx) {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::ShowAchievements => {
+                if menu::achievements(&self.ecs, ctx) {
+                    newrunstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Achievements,
+                    };
+                }
+            }
+            RunState::ShowOptions { selected } => match menu::options(&mut self.ecs, ctx, selected)
+            {
+                menu::OptionsResult::NoResponse => {}
+                menu::OptionsResult::Selected { selected } => {
+                    newrunstate = RunState::ShowOptions { selected }
+                }
+                menu::OptionsResult::Close => {
+                    newrunstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Options,
+                    }
+                }
+            },
             RunState::PreRun => {
                 self.run_systems();
                 self.ecs.maintain();
                 newrunstate = RunState::AwaitingInput;
             }
             RunState::AwaitingInput => {
-                newrunstate = player::player_input(self, ctx);
+                newrunstate = if !auto_move::is_active(&self.ecs) {
+                    player::player_input(self, ctx)
+                } else if ctx.key.is_some() || ctx.left_click {
+                    // Any key or click takes back control
+                    auto_move::stop(
+                        &mut self.ecs,
+                        gamelog::LogMessage::new(LogCategory::System).text("You stop."),
+                    );
+                    RunState::AwaitingInput
+                } else {
+                    auto_move::step(&mut self.ecs)
+                };
             }
             RunState::PlayerTurn => {
+                let turn = {
+                    let mut stats = self.ecs.write_resource::<morgue::RunStatistics>();
+                    stats.turns += 1;
+                    stats.turns
+                };
+                self.ecs.write_resource::<gamelog::GameLog>().turn = turn;
                 self.run_systems();
                 self.ecs.maintain();
                 match *self.ecs.fetch::<RunState>() {
                     RunState::MagicMapReveal { .. } => {
                         newrunstate = RunState::MagicMapReveal { row: 0 }
                     }
+                    RunState::TownPortal => newrunstate = RunState::TownPortal,
                     _ => newrunstate = RunState::MonsterTurn,
                 }
             }
@@ -322,6 +560,16 @@ impl GameState for State {
             RunState::MonsterTurn => {
                 self.run_systems();
                 self.ecs.maintain();
+                if self.overloaded() && self.ecs.fetch::<morgue::RunStatistics>().turns % 2 == 0 {
+                    // An overloaded player is slow: every other turn, monsters move twice
+                    self.run_systems();
+                    self.ecs.maintain();
+                }
+                let autosave = self.ecs.fetch::<settings::Settings>().autosave_turns;
+                let turns = self.ecs.fetch::<morgue::RunStatistics>().turns;
+                if autosave > 0 && turns % autosave == 0 {
+                    saveload_system::save_game(&mut self.ecs);
+                }
                 newrunstate = RunState::AwaitingInput;
             }
             RunState::ShowRemoveItem => {
@@ -342,10 +590,13 @@ impl GameState for State {
                     }
                 }
             }
-            RunState::GameOver => {
-                let result = gui::game_over(ctx);
+            RunState::GameOver { scroll } => {
+                let result = gui::game_over(&self.ecs, ctx, scroll);
                 match result {
                     gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::Scroll(scroll) => {
+                        newrunstate = RunState::GameOver { scroll }
+                    }
                     gui::GameOverResult::QuitToMenu => {
                         self.game_over_cleanup();
                         newrunstate = RunState::MainMenu {
@@ -354,20 +605,100 @@ impl GameState for State {
                     }
                 }
             }
-            RunState::ShowDropItem => {
-                let result = gui::drop_item_menu(self, ctx);
-                match result.0 {
-                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToDropItem { item: item_entity },
-                            )
-                            .expect("Unable to insert intent");
+            RunState::ShowLog { scroll, filter } => {
+                match gui::show_log(&self.ecs, ctx, scroll, filter) {
+                    gui::LogViewResult::NoResponse => {}
+                    gui::LogViewResult::Changed { scroll, filter } => {
+                        newrunstate = RunState::ShowLog { scroll, filter }
+                    }
+                    gui::LogViewResult::Close => newrunstate = RunState::AwaitingInput,
+                }
+            }
+            RunState::ShowDropItem { page, sort } => {
+                match gui::drop_item_menu(self, ctx, page, sort) {
+                    gui::StackMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::StackMenuResult::NoResponse => {}
+                    gui::StackMenuResult::Page(page) => {
+                        newrunstate = RunState::ShowDropItem { page, sort }
+                    }
+                    gui::StackMenuResult::Tab => {
+                        newrunstate = RunState::ShowDropItem {
+                            page: 0,
+                            sort: sort.next(),
+                        }
+                    }
+                    gui::StackMenuResult::Selected { item, count } => {
+                        if count > 1 {
+                            newrunstate = RunState::ShowDropCount { item, count };
+                        } else {
+                            self.drop_items(item, 1);
+                            newrunstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowPickup { page } => match gui::pickup_menu(&self.ecs, ctx, page) {
+                gui::StackMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                gui::StackMenuResult::NoResponse | gui::StackMenuResult::Tab => {}
+                gui::StackMenuResult::Page(page) => newrunstate = RunState::ShowPickup { page },
+                gui::StackMenuResult::Selected { item, count } => {
+                    let player_entity = *self.ecs.fetch::<Entity>();
+                    self.ecs
+                        .write_storage::<WantsToPickupItem>()
+                        .insert(
+                            player_entity,
+                            WantsToPickupItem {
+                                collected_by: player_entity,
+                                item,
+                                count,
+                            },
+                        )
+                        .expect("Unable to insert want to pickup");
+                    newrunstate = RunState::PlayerTurn;
+                }
+            },
+            RunState::ShowContainer {
+                container,
+                mode,
+                page,
+            } => match gui::container_menu(&self.ecs, ctx, container, mode, page) {
+                gui::StackMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                gui::StackMenuResult::NoResponse => {}
+                gui::StackMenuResult::Page(page) => {
+                    newrunstate = RunState::ShowContainer {
+                        container,
+                        mode,
+                        page,
+                    }
+                }
+                gui::StackMenuResult::Tab => {
+                    newrunstate = RunState::ShowContainer {
+                        container,
+                        mode: match mode {
+                            gui::ContainerMode::Take => gui::ContainerMode::Put,
+                            gui::ContainerMode::Put => gui::ContainerMode::Take,
+                        },
+                        page: 0,
+                    }
+                }
+                gui::StackMenuResult::Selected { item, count } => match mode {
+                    gui::ContainerMode::Take => {
+                        container::take(&mut self.ecs, container, item, count)
+                    }
+                    gui::ContainerMode::Put => {
+                        container::put(&mut self.ecs, container, item, count)
+                    }
+                },
+            },
+            RunState::ShowDropCount { item, count } => {
+                match gui::drop_count_menu(&self.ecs, ctx, item, count) {
+                    gui::DropCountResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::DropCountResult::NoResponse => {}
+                    gui::DropCountResult::Changed(count) => {
+                        newrunstate = RunState::ShowDropCount { item, count }
+                    }
+                    gui::DropCountResult::Drop(count) => {
+                        self.drop_items(item, count);
                         newrunstate = RunState::PlayerTurn;
                     }
                 }
@@ -388,6 +719,7 @@ impl GameState for State {
                         }
                     }
                     gui::ItemMenuResult::Selected => {
+                        self.record_item_use(item);
                         let mut intent = self.ecs.write_storage::<WantsToUseItem>();
                         intent
                             .insert(
@@ -402,13 +734,103 @@ impl GameState for State {
                     }
                 }
             }
-            RunState::ShowInventory => {
-                let result = gui::show_inventory(self, ctx);
+            RunState::LookMode { cursor } => {
+                let result = gui::look_mode(&self.ecs, ctx, cursor);
+                newrunstate = match result.0 {
+                    gui::ItemMenuResult::Cancel => RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => RunState::LookMode { cursor: result.1 },
+                    gui::ItemMenuResult::Selected => auto_move::start(
+                        &mut self.ecs,
+                        auto_move::AutoMode::Travel {
+                            destination: result.1,
+                        },
+                    ),
+                };
+            }
+            RunState::ShowSpellTargeting {
+                range,
+                spell,
+                target,
+            } => {
+                let result = gui::ranged_target(self, ctx, range, target);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {
+                        newrunstate = RunState::ShowSpellTargeting {
+                            range,
+                            spell,
+                            target: result.2,
+                        }
+                    }
+                    gui::ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToCastSpell>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToCastSpell {
+                                    spell: spell_system::SPELLS[spell].name.to_string(),
+                                    target: result.1,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowSpells => {
+                let result = gui::show_spells(self, ctx);
                 match result.0 {
                     gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
+                        let spell = result.1.unwrap();
+                        if let Some(range) = spell_system::SPELLS[spell].range {
+                            newrunstate = RunState::ShowSpellTargeting {
+                                range,
+                                spell,
+                                target: None,
+                            };
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToCastSpell>();
+                            intent
+                                .insert(
+                                    *self.ecs.fetch::<Entity>(),
+                                    WantsToCastSpell {
+                                        spell: spell_system::SPELLS[spell].name.to_string(),
+                                        target: None,
+                                    },
+                                )
+                                .expect("Unable to insert intent");
+                            newrunstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowInventory { page, sort } => {
+                match gui::show_inventory(self, ctx, page, sort) {
+                    gui::StackMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::StackMenuResult::NoResponse => {}
+                    gui::StackMenuResult::Page(page) => {
+                        newrunstate = RunState::ShowInventory { page, sort }
+                    }
+                    gui::StackMenuResult::Tab => {
+                        newrunstate = RunState::ShowInventory {
+                            page: 0,
+                            sort: sort.next(),
+                        }
+                    }
+                    gui::StackMenuResult::Selected {
+                        item: item_entity, ..
+                    } if self.ecs.read_storage::<Container>().contains(item_entity) => {
+                        newrunstate = RunState::ShowContainer {
+                            container: item_entity,
+                            mode: gui::ContainerMode::Take,
+                            page: 0,
+                        };
+                    }
+                    gui::StackMenuResult::Selected {
+                        item: item_entity, ..
+                    } => {
                         let is_ranged = self.ecs.read_storage::<Ranged>();
                         let is_item_ranged = is_ranged.get(item_entity);
                         if let Some(is_item_ranged) = is_item_ranged {
@@ -418,6 +840,7 @@ impl GameState for State {
                                 target: None,
                             };
                         } else {
+                            self.record_item_use(item_entity);
                             let mut intent = self.ecs.write_storage::<WantsToUseItem>();
                             intent
                                 .insert(
@@ -444,6 +867,64 @@ impl GameState for State {
                 self.goto_next_level();
                 newrunstate = RunState::PreRun;
             }
+            RunState::TownPortal => {
+                self.goto_town();
+                newrunstate = if self.won() {
+                    self.end_run(true);
+                    RunState::Victory { scroll: 0 }
+                } else {
+                    RunState::PreRun
+                };
+            }
+            RunState::TakePortal { depth } => {
+                self.take_portal(depth);
+                newrunstate = if self.won() {
+                    self.end_run(true);
+                    RunState::Victory { scroll: 0 }
+                } else {
+                    RunState::PreRun
+                };
+            }
+            RunState::Victory { scroll } => {
+                let result = gui::victory(&self.ecs, ctx, scroll);
+                match result {
+                    gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::Scroll(scroll) => {
+                        newrunstate = RunState::Victory { scroll }
+                    }
+                    gui::GameOverResult::QuitToMenu => {
+                        self.game_over_cleanup();
+                        newrunstate = RunState::MainMenu {
+                            menu_selection: gui::MainMenuSelection::NewGame,
+                        };
+                    }
+                }
+            }
+            RunState::ShowVendor { vendor, mode } => {
+                let result = gui::show_vendor_menu(self, ctx, vendor, mode);
+                match result.0 {
+                    gui::VendorResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::VendorResult::NoResponse => {}
+                    gui::VendorResult::Sell => {
+                        trade::sell(&mut self.ecs, result.1.unwrap());
+                    }
+                    gui::VendorResult::Buy => {
+                        trade::buy(&mut self.ecs, &result.2.unwrap());
+                    }
+                    gui::VendorResult::BuyMode => {
+                        newrunstate = RunState::ShowVendor {
+                            vendor,
+                            mode: gui::VendorMode::Buy,
+                        }
+                    }
+                    gui::VendorResult::SellMode => {
+                        newrunstate = RunState::ShowVendor {
+                            vendor,
+                            mode: gui::VendorMode::Sell,
+                        }
+                    }
+                }
+            }
             _ => {
                 map::draw_map(&self.ecs, ctx);
 
@@ -469,26 +950,44 @@ impl GameState for State {
             }
         }
 
-        if damage_system::delete_the_dead(&mut self.ecs) == Some(RunState::GameOver) {
-            newrunstate = RunState::GameOver;
+        if let Some(RunState::GameOver { .. }) = damage_system::delete_the_dead(&mut self.ecs) {
+            // Only write the morgue file once, on the turn the player dies
+            if !matches!(newrunstate, RunState::GameOver { .. }) {
+                self.end_run(false);
+                newrunstate = RunState::GameOver { scroll: 0 };
+            }
         }
 
         {
             let mut runwriter = self.ecs.write_resource::<RunState>();
             *runwriter = newrunstate;
         }
+        speech::speak(&mut self.ecs, ctx);
     }
 }
 
 fn main() -> rltk::BError {
-    use rltk::RltkBuilder;
-    /*let mut context = RltkBuilder::simple80x50()
-        .with_title("Roguelike Tutorial")
-        .build()?;
-    context.with_post_scanlines(true);*/
-    let context = RltkBuilder::simple80x50()
-        .with_title("Wonderful RustMUD")
-        .build()?;
+    let (settings, settings_problem) = settings::Settings::load();
+    // Some settings can also be picked for just this run from the command line. They go in a
+    // copy, so saving from the options menu never keeps them.
+    let mut run_settings = settings.clone();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--tiles" => run_settings.graphics = settings::Graphics::Tiles,
+            "--ascii" => run_settings.graphics = settings::Graphics::Ascii,
+            "--screen-reader" => run_settings.screen_reader = true,
+            _ => {}
+        }
+    }
+    // A terminal can only show text, so the terminal build ignores the tile setting
+    let (tileset, tileset_problem) = match run_settings.graphics {
+        settings::Graphics::Tiles if !cfg!(feature = "terminal") => {
+            let (tileset, problem) = tileset::Tileset::load();
+            (Some(tileset), problem)
+        }
+        _ => (None, None),
+    };
+    let context = run_settings.build_context(tileset.as_ref())?;
     let mut gs = State { ecs: World::new() };
     gs.ecs.register::<Position>();
     gs.ecs.register::<Renderable>();
@@ -527,29 +1026,73 @@ fn main() -> rltk::BError {
     gs.ecs.register::<EntryTrigger>();
     gs.ecs.register::<EntityMoved>();
     gs.ecs.register::<SingleActivation>();
+    gs.ecs.register::<Purse>();
+    gs.ecs.register::<Gold>();
+    gs.ecs.register::<ItemValue>();
+    gs.ecs.register::<ItemWeight>();
+    gs.ecs.register::<Encumbrance>();
+    gs.ecs.register::<Container>();
+    gs.ecs.register::<InContainer>();
+    gs.ecs.register::<Locked>();
+    gs.ecs.register::<Trapped>();
+    gs.ecs.register::<CarriesItems>();
+    gs.ecs.register::<Vendor>();
+    gs.ecs.register::<Bystander>();
+    gs.ecs.register::<TownPortal>();
+    gs.ecs.register::<Portal>();
+    gs.ecs.register::<LootTable>();
+    gs.ecs.register::<LeavesCorpse>();
+    gs.ecs.register::<Mana>();
+    gs.ecs.register::<KnownSpells>();
+    gs.ecs.register::<TeachesSpell>();
+    gs.ecs.register::<WantsToCastSpell>();
+    gs.ecs.register::<MonsterAbilities>();
+    gs.ecs.register::<WantsToSummon>();
+    gs.ecs.register::<QuestItem>();
 
     gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
-    let map = map::Map::new_map_rooms_and_corridors(1);
-
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
-    for room in map.rooms.iter().skip(1) {
-        spawner::spawn_room(&mut gs.ecs, room, 1);
-    }
-
+    gs.ecs.insert(Map::new(1));
+    gs.ecs.insert(Point::new(0, 0));
+    let player_entity = spawner::player(&mut gs.ecs, 0, 0);
+    gs.ecs.insert(player_entity);
     gs.ecs.insert(RunState::MainMenu {
         menu_selection: gui::MainMenuSelection::NewGame,
     });
-    gs.ecs.insert(map);
-    let (player_x, player_y) = gs.ecs.fetch::<Map>().rooms[0].center();
-    gs.ecs.insert(Point::new(player_x, player_y));
-
-    let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
-    gs.ecs.insert(player_entity);
-    gs.ecs.insert(gamelog::GameLog {
-        entries: vec!["Welcome to Rusty Roguelike".to_string()],
-    });
+    let bindings = keybindings::KeyBindings::load();
+    let mut log = gamelog::GameLog::default();
+    log.set_max_entries(settings.log_length);
+    log.push(LogCategory::System, "Welcome to Rusty Roguelike");
+    for problem in bindings
+        .problems
+        .iter()
+        .chain(tileset_problem.iter())
+        .chain(settings_problem.iter())
+    {
+        log.push(LogCategory::System, problem);
+    }
+    if let Some(tileset) = tileset {
+        gs.ecs.insert(tileset);
+    }
+    gs.ecs.insert(bindings);
+    gs.ecs.insert(log);
     gs.ecs.insert(particle_system::ParticleBuilder::new());
+    gs.start_run();
+    gs.ecs.insert(morgue::Morgue::default());
+    gs.ecs.insert(achievements::Achievements::load());
+    gs.ecs
+        .insert(auto_move::AutoMove::new(settings.autoexplore_pickup));
+    gs.ecs.insert(palette::Palette {
+        theme: settings.theme,
+    });
+    speech::enable(
+        &mut gs.ecs,
+        run_settings.screen_reader,
+        &run_settings.screen_reader_output,
+    );
+    gs.ecs.insert(settings);
     gs.ecs.insert(rex_assets::RexAssets::new());
 
+    gs.generate_world_map(map_builder::TOWN_DEPTH);
+
     rltk::main_loop(context, gs)
 }
@@ -1,3 +1,4 @@
+use super::palette::{Colour, Palette};
 use super::rect::Rect;
 use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB};
 use serde::{Deserialize, Serialize};
@@ -144,10 +145,9 @@ impl Map {
         }
     }
 
-    /// Makes a new map using the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/
-    /// This gives a handful of random rooms and corridors joining them together.
-    pub fn new_map_rooms_and_corridors(new_depth: i32) -> Map {
-        let mut map = Map {
+    /// Makes a map that is solid wall everywhere, ready to be carved into.
+    pub fn new(new_depth: i32) -> Map {
+        Map {
             tiles: vec![TileType::Wall; MAPCOUNT],
             rooms: Vec::new(),
             width: MAPWIDTH as i32,
@@ -158,14 +158,18 @@ impl Map {
             tile_content: vec![Vec::new(); MAPCOUNT],
             depth: new_depth,
             bloodstains: HashSet::new(),
-        };
+        }
+    }
+
+    /// Makes a new map using the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/
+    /// This gives a handful of random rooms and corridors joining them together.
+    pub fn new_map_rooms_and_corridors(new_depth: i32, rng: &mut RandomNumberGenerator) -> Map {
+        let mut map = Map::new(new_depth);
 
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
 
-        let mut rng = RandomNumberGenerator::new();
-
         for _i in 0..MAX_ROOMS {
             let w = rng.range(MIN_SIZE, MAX_SIZE);
             let h = rng.range(MIN_SIZE, MAX_SIZE);
@@ -211,39 +215,44 @@ impl Map {
     }
 }
 
+/// How a revealed tile looks as text: its glyph, foreground and background.
+pub fn tile_glyph(map: &Map, palette: &Palette, idx: usize) -> (rltk::FontCharType, RGB, RGB) {
+    let glyph;
+    let mut fg;
+    let mut bg = RGB::from_f32(0., 0., 0.);
+    match map.tiles[idx] {
+        TileType::Floor => {
+            glyph = rltk::to_cp437('.');
+            fg = palette.get(Colour::Floor);
+        }
+        TileType::Wall => {
+            glyph = wall_glyph(map, idx as i32 % map.width, idx as i32 / map.width);
+            fg = palette.get(Colour::Wall);
+        }
+        TileType::DownStairs => {
+            glyph = rltk::to_cp437('>');
+            fg = palette.get(Colour::Stairs);
+        }
+    }
+    if map.bloodstains.contains(&idx) {
+        bg = palette.get(Colour::Bloodstain);
+    }
+    if !map.visible_tiles[idx] {
+        fg = fg.to_greyscale() * palette.get(Colour::Visited);
+        bg = RGB::from_f32(0., 0., 0.); // Don't show stains out of visual range
+    }
+    (glyph, fg, bg)
+}
+
 pub fn draw_map(ecs: &super::World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
 
     let mut y = 0;
     let mut x = 0;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending upon the tile type
-
+    for idx in 0..map.tiles.len() {
         if map.revealed_tiles[idx] {
-            let glyph;
-            let mut fg;
-            let mut bg = RGB::from_f32(0., 0., 0.);
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
-                }
-                TileType::Wall => {
-                    glyph = wall_glyph(&*map, x, y);
-                    fg = RGB::from_f32(0., 1.0, 0.);
-                }
-                TileType::DownStairs => {
-                    glyph = rltk::to_cp437('>');
-                    fg = RGB::from_f32(0., 1.0, 1.0);
-                }
-            }
-            if map.bloodstains.contains(&idx) {
-                bg = RGB::from_f32(0.75, 0., 0.);
-            }
-            if !map.visible_tiles[idx] {
-                fg = fg.to_greyscale();
-                bg = RGB::from_f32(0., 0., 0.); // Don't show stains out of visual range
-            }
+            let (glyph, fg, bg) = tile_glyph(&map, &palette, idx);
             ctx.set(x, y, fg, bg, glyph);
         }
 
@@ -256,9 +265,11 @@ pub fn draw_map(ecs: &super::World, ctx: &mut Rltk) {
     }
 }
 
-fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
+/// Which neighbours of a wall are known walls too: north 1, south 2, west 4 and east 8.
+/// None on the edge of the map, where there are no neighbours to look at.
+pub fn wall_mask(map: &Map, x: i32, y: i32) -> Option<u8> {
     if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 as i32 {
-        return 35;
+        return None;
     }
     let mut mask: u8 = 0;
 
@@ -274,6 +285,14 @@ fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
     if is_revealed_and_wall(map, x + 1, y) {
         mask += 8;
     }
+    Some(mask)
+}
+
+fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
+    let mask = match wall_mask(map, x, y) {
+        Some(mask) => mask,
+        None => return 35,
+    };
 
     match mask {
         0 => 9,    // Pillar because we can't see neighbors
@@ -0,0 +1,118 @@
+use super::{
+    map::Map,
+    prefab_builder,
+    prefabs::{self, PrefabLevel, PrefabSection},
+    rect::Rect,
+    spawner, Position,
+};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+/// A freshly generated level, before anything has been placed in the world.
+pub struct BuiltLevel {
+    pub map: Map,
+    pub starting_position: Position,
+    /// Rooms that should be filled by the random spawner.
+    pub spawn_rooms: Vec<Rect>,
+    /// Entities requested by prefabs, as (map index, spawn name).
+    pub spawn_list: Vec<(usize, String)>,
+}
+
+impl BuiltLevel {
+    pub fn spawn_entities(&self, ecs: &mut World) {
+        let reserved: Vec<usize> = self.spawn_list.iter().map(|(idx, _)| *idx).collect();
+        spawner::populate_level(ecs, &self.map, &self.spawn_rooms, &reserved);
+        for spawn in self.spawn_list.iter() {
+            spawner::spawn_entity(ecs, spawn.0, &spawn.1);
+        }
+    }
+}
+
+/// Hand-made whole levels, and the depths at which they may replace a generated level.
+const PREFAB_LEVELS: &[(PrefabLevel, i32)] = &[(prefabs::ANCIENT_HALL, 2)];
+
+/// Sections that may be stamped over part of a generated level.
+const PREFAB_SECTIONS: &[PrefabSection] = &[
+    prefabs::ORC_CAMP,
+    prefabs::FORGOTTEN_SHRINE,
+    prefabs::GOBLIN_WARRENS,
+];
+
+/// The depth of the town on the surface.
+pub const TOWN_DEPTH: i32 = 0;
+
+/// The bottom of the dungeon, where the Lich keeps the amulet.
+pub const FINAL_DEPTH: i32 = 10;
+
+/// Picks and builds the level for a given depth: the town on the surface and the Lich's lair at
+/// the bottom; in between usually rooms and corridors with the odd vault or section stamped
+/// in, occasionally a level designed entirely by hand.
+pub fn build_level(new_depth: i32, rng: &mut RandomNumberGenerator) -> BuiltLevel {
+    if new_depth == TOWN_DEPTH || new_depth >= FINAL_DEPTH {
+        let level = if new_depth == TOWN_DEPTH {
+            &prefabs::TOWN
+        } else {
+            &prefabs::LICH_LAIR
+        };
+        let (map, starting_position, spawn_list) = prefab_builder::load_rex_level(level, new_depth);
+        return BuiltLevel {
+            map,
+            starting_position,
+            spawn_rooms: Vec::new(),
+            spawn_list,
+        };
+    }
+
+    let prefab_levels: Vec<&PrefabLevel> = PREFAB_LEVELS
+        .iter()
+        .filter(|(_, first_depth)| new_depth >= *first_depth)
+        .map(|(level, _)| level)
+        .collect();
+    if !prefab_levels.is_empty() && rng.roll_dice(1, 8) == 1 {
+        let level = prefab_levels[(rng.roll_dice(1, prefab_levels.len() as i32) - 1) as usize];
+        let (map, starting_position, spawn_list) = prefab_builder::load_rex_level(level, new_depth);
+        return BuiltLevel {
+            map,
+            starting_position,
+            spawn_rooms: Vec::new(),
+            spawn_list,
+        };
+    }
+
+    let mut map = Map::new_map_rooms_and_corridors(new_depth, rng);
+    let (start_x, start_y) = map.rooms[0].center();
+    let starting_position = Position {
+        x: start_x,
+        y: start_y,
+    };
+    // Nothing spawns in the player's starting room
+    let mut spawn_rooms: Vec<Rect> = map.rooms.iter().skip(1).copied().collect();
+    let mut spawn_list = Vec::new();
+
+    for section in PREFAB_SECTIONS.iter() {
+        if new_depth >= section.first_depth && rng.roll_dice(1, 4) == 1 {
+            prefab_builder::apply_section(
+                &mut map,
+                section,
+                &starting_position,
+                &mut spawn_rooms,
+                &mut spawn_list,
+            );
+        }
+    }
+    prefab_builder::apply_room_vaults(
+        &mut map,
+        rng,
+        &starting_position,
+        &mut spawn_rooms,
+        &mut spawn_list,
+        prefabs::ROOM_VAULTS,
+    );
+
+    BuiltLevel {
+        map,
+        starting_position,
+        spawn_rooms,
+        spawn_list,
+    }
+}
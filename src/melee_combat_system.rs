@@ -1,6 +1,11 @@
 use super::{
-    gamelog::GameLog, particle_system::ParticleBuilder, CombatStats, DefenseBonus, Equipped,
-    HungerClock, HungerState, MeleePowerBonus, Name, Position, SufferDamage, WantsToMelee,
+    encumbrance_system,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    morgue::RunStatistics,
+    palette::Colour,
+    particle_system::ParticleBuilder,
+    CombatStats, DefenseBonus, Encumbrance, Equipped, HungerClock, HungerState, MeleePowerBonus,
+    Name, Position, SufferDamage, WantsToMelee,
 };
 //use rltk::console;
 use specs::prelude::*;
@@ -21,6 +26,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, HungerClock>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStatistics>,
+        ReadStorage<'a, Encumbrance>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -37,6 +45,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut particle_builder,
             positions,
             hunger_clocks,
+            player_entity,
+            mut run_stats,
+            encumbrance,
         ) = data;
 
         for (entity, wants_melee, name, stats) in
@@ -62,6 +73,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
                             offensive_bonus += 1;
                         }
                     }
+                    if let Some(load) = encumbrance.get(entity) {
+                        offensive_bonus -= encumbrance_system::attack_penalty(load.state);
+                    }
 
                     let mut defensive_bonus = 0;
                     for (_item_entity, defense_bonus, equipped_by) in
@@ -77,7 +91,7 @@ impl<'a> System<'a> for MeleeCombatSystem {
                         particle_builder.request(
                             pos.x,
                             pos.y,
-                            rltk::RGB::named(rltk::ORANGE),
+                            Colour::Warning,
                             rltk::RGB::named(rltk::BLACK),
                             rltk::to_cp437('‼'),
                             200.0,
@@ -90,16 +104,32 @@ impl<'a> System<'a> for MeleeCombatSystem {
                     );
 
                     if damage == 0 {
-                        log.entries.push(format!(
-                            "{} is unable to hurt {}",
-                            &name.name, &target_name.name
-                        ));
+                        log.add(
+                            LogMessage::new(LogCategory::Combat)
+                                .creature(&name.name, entity == *player_entity)
+                                .text(" is unable to hurt ")
+                                .creature(&target_name.name, wants_melee.target == *player_entity),
+                        );
                     } else {
-                        log.entries.push(format!(
-                            "{} hits {}, for {} hp.",
-                            &name.name, &target_name.name, damage
-                        ));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                        log.add(
+                            LogMessage::new(LogCategory::Combat)
+                                .creature(&name.name, entity == *player_entity)
+                                .text(" hits ")
+                                .creature(&target_name.name, wants_melee.target == *player_entity)
+                                .text(format!(", for {} hp.", damage)),
+                        );
+                        SufferDamage::new_damage(
+                            &mut inflict_damage,
+                            wants_melee.target,
+                            damage,
+                            entity == *player_entity,
+                        );
+                        if wants_melee.target == *player_entity {
+                            run_stats.last_hurt_by = Some(name.name.clone());
+                        }
+                        if entity == *player_entity {
+                            run_stats.damage_dealt += damage;
+                        }
                     }
                 }
             }
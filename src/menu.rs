@@ -1,9 +1,18 @@
+use super::achievements::{Achievements, ACHIEVEMENTS};
+use super::auto_move::AutoMove;
 use super::gamelog;
+use super::highscores;
+use super::keybindings::{KeyBindings, Preset};
+use super::palette::{Colour, Palette, THEMES};
+use super::settings::{Font, Graphics, Settings};
+use super::speech;
+use super::tileset;
 use super::{gui::MainMenuResult, gui::MainMenuSelection, RexAssets, RunState, State};
-use rltk::{Rltk, VirtualKeyCode, RGB};
+use rltk::{Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
 pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
+    let palette = gs.ecs.fetch::<Palette>();
     let save_exists = super::saveload_system::does_save_exist();
     let runstate = gs.ecs.fetch::<RunState>();
     let assets = gs.ecs.fetch::<RexAssets>();
@@ -13,26 +22,26 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
         24,
         18,
         31,
-        10,
-        RGB::named(rltk::WHEAT),
-        RGB::named(rltk::BLACK),
+        11,
+        palette.get(Colour::Border),
+        palette.get(Colour::Background),
     );
     ctx.print_color_centered(
         20,
-        RGB::named(rltk::YELLOW),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
         "Rust Roguelike Tutorial",
     );
     ctx.print_color_centered(
         21,
-        RGB::named(rltk::CYAN),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Highlight),
+        palette.get(Colour::Background),
         "by Herbert Wolverson",
     );
     ctx.print_color_centered(
         22,
-        RGB::named(rltk::GRAY),
-        RGB::named(rltk::BLACK),
+        palette.get(Colour::Muted),
+        palette.get(Colour::Background),
         "Use Up/Down Arrows and Enter",
     );
 
@@ -41,53 +50,27 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
         menu_selection: selection,
     } = *runstate
     {
-        if selection == MainMenuSelection::NewGame {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        } else {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::WHITE),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        }
-        y += 1;
-
-        if save_exists {
-            if selection == MainMenuSelection::LoadGame {
-                ctx.print_color_centered(
-                    y,
-                    RGB::named(rltk::MAGENTA),
-                    RGB::named(rltk::BLACK),
-                    "Load Game",
-                );
-            } else {
-                ctx.print_color_centered(
-                    y,
-                    RGB::named(rltk::WHITE),
-                    RGB::named(rltk::BLACK),
-                    "Load Game",
-                );
+        let entries = [
+            MainMenuSelection::NewGame,
+            MainMenuSelection::LoadGame,
+            MainMenuSelection::HighScores,
+            MainMenuSelection::Achievements,
+            MainMenuSelection::Options,
+            MainMenuSelection::Quit,
+        ];
+        for entry in entries.iter() {
+            if *entry == MainMenuSelection::LoadGame && !save_exists {
+                continue;
             }
+            let fg = if *entry == selection {
+                palette.get(Colour::Selected)
+            } else {
+                palette.get(Colour::Text)
+            };
+            ctx.print_color_centered(y, fg, palette.get(Colour::Background), entry.label());
             y += 1;
         }
 
-        if selection == MainMenuSelection::Quit {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Quit",
-            );
-        } else {
-            ctx.print_color_centered(y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
-        }
-
         match ctx.key {
             None => {
                 return MainMenuResult::NoSelection {
@@ -101,11 +84,21 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                     }
                 }
                 VirtualKeyCode::Up => {
-                    let newselection;
+                    let mut newselection;
                     match selection {
                         MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
                         MainMenuSelection::LoadGame => newselection = MainMenuSelection::NewGame,
-                        MainMenuSelection::Quit => newselection = MainMenuSelection::LoadGame,
+                        MainMenuSelection::HighScores => newselection = MainMenuSelection::LoadGame,
+                        MainMenuSelection::Achievements => {
+                            newselection = MainMenuSelection::HighScores
+                        }
+                        MainMenuSelection::Options => {
+                            newselection = MainMenuSelection::Achievements
+                        }
+                        MainMenuSelection::Quit => newselection = MainMenuSelection::Options,
+                    }
+                    if newselection == MainMenuSelection::LoadGame && !save_exists {
+                        newselection = MainMenuSelection::NewGame;
                     }
                     return MainMenuResult::NoSelection {
                         selected: newselection,
@@ -115,11 +108,18 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                     let mut newselection;
                     match selection {
                         MainMenuSelection::NewGame => newselection = MainMenuSelection::LoadGame,
-                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::Quit,
+                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::HighScores,
+                        MainMenuSelection::HighScores => {
+                            newselection = MainMenuSelection::Achievements
+                        }
+                        MainMenuSelection::Achievements => {
+                            newselection = MainMenuSelection::Options
+                        }
+                        MainMenuSelection::Options => newselection = MainMenuSelection::Quit,
                         MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame,
                     }
                     if newselection == MainMenuSelection::LoadGame && !save_exists {
-                        newselection = MainMenuSelection::Quit;
+                        newselection = MainMenuSelection::HighScores;
                     }
                     return MainMenuResult::NoSelection {
                         selected: newselection,
@@ -143,3 +143,348 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
         selected: MainMenuSelection::NewGame,
     }
 }
+
+/// How many runs fit on the high-score screen.
+const HIGH_SCORES_SHOWN: usize = 20;
+
+/// Shows the best runs so far. Returns true once the player is done looking.
+pub fn high_scores(ecs: &World, ctx: &mut Rltk) -> bool {
+    let palette = ecs.fetch::<Palette>();
+    let scores = highscores::load_scores();
+    tileset::clear_screen(ctx);
+    ctx.draw_box_double(
+        2,
+        4,
+        75,
+        HIGH_SCORES_SHOWN as i32 + 6,
+        palette.get(Colour::Border),
+        palette.get(Colour::Background),
+    );
+    ctx.print_color_centered(
+        6,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        "High Scores",
+    );
+    ctx.print_color(
+        4,
+        8,
+        palette.get(Colour::Muted),
+        palette.get(Colour::Background),
+        format!(
+            "{:>2}  {:<14} {:>6} {:>5} {:>5} {:>5} {:>5}  {:<10} {:>10}",
+            "#", "Name", "Score", "Depth", "Kills", "XP", "Gold", "Date", "Seed"
+        ),
+    );
+
+    if scores.is_empty() {
+        ctx.print_color_centered(
+            10,
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
+            "No runs recorded yet",
+        );
+    }
+    for (i, entry) in scores.iter().take(HIGH_SCORES_SHOWN).enumerate() {
+        let colour = if entry.won {
+            palette.get(Colour::Gold)
+        } else {
+            palette.get(Colour::Text)
+        };
+        let name: String = entry.name.chars().take(14).collect();
+        ctx.print_color(
+            4,
+            9 + i as i32,
+            colour,
+            palette.get(Colour::Background),
+            format!(
+                "{:>2}  {:<14} {:>6} {:>5} {:>5} {:>5} {:>5}  {:<10} {:>10}",
+                i + 1,
+                name,
+                entry.score,
+                entry.depth,
+                entry.kills,
+                entry.xp,
+                entry.gold,
+                entry.date,
+                entry.seed
+            ),
+        );
+    }
+
+    ctx.print_color_centered(
+        HIGH_SCORES_SHOWN as i32 + 9,
+        palette.get(Colour::Muted),
+        palette.get(Colour::Background),
+        "Winning runs are in gold. Press ESCAPE to return",
+    );
+
+    matches!(
+        ctx.key,
+        Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::Return)
+    )
+}
+
+/// Lists every achievement, lit up if it has ever been unlocked. Returns true once the player
+/// is done looking.
+pub fn achievements(ecs: &World, ctx: &mut Rltk) -> bool {
+    let palette = ecs.fetch::<Palette>();
+    let unlocked = ecs.fetch::<Achievements>();
+    tileset::clear_screen(ctx);
+    ctx.draw_box_double(
+        2,
+        4,
+        75,
+        ACHIEVEMENTS.len() as i32 + 6,
+        palette.get(Colour::Border),
+        palette.get(Colour::Background),
+    );
+    ctx.print_color_centered(
+        6,
+        palette.get(Colour::Title),
+        palette.get(Colour::Background),
+        format!(
+            "Achievements ({} of {})",
+            unlocked.unlocked.len(),
+            ACHIEVEMENTS.len()
+        ),
+    );
+
+    for (i, achievement) in ACHIEVEMENTS.iter().enumerate() {
+        let y = 8 + i as i32;
+        match unlocked.unlocked.get(achievement.name) {
+            Some(date) => {
+                ctx.print_color(
+                    4,
+                    y,
+                    palette.get(Colour::Gold),
+                    palette.get(Colour::Background),
+                    achievement.name,
+                );
+                ctx.print_color(
+                    66,
+                    y,
+                    palette.get(Colour::Muted),
+                    palette.get(Colour::Background),
+                    date,
+                );
+            }
+            None => ctx.print_color(
+                4,
+                y,
+                palette.get(Colour::Disabled),
+                palette.get(Colour::Background),
+                achievement.name,
+            ),
+        }
+        ctx.print_color(
+            24,
+            y,
+            palette.get(Colour::Text),
+            palette.get(Colour::Background),
+            achievement.description,
+        );
+    }
+
+    ctx.print_color_centered(
+        ACHIEVEMENTS.len() as i32 + 11,
+        palette.get(Colour::Muted),
+        palette.get(Colour::Background),
+        "Press ESCAPE to return",
+    );
+
+    matches!(
+        ctx.key,
+        Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::Return)
+    )
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum OptionsResult {
+    NoResponse,
+    Selected { selected: usize },
+    Close,
+}
+
+const LOG_LENGTHS: &[usize] = &[100, 250, 500, 1000];
+const AUTOSAVE_TURNS: &[i32] = &[0, 50, 100, 250, 500];
+const OPTION_COUNT: usize = 10;
+
+/// The value after (or before) `current` in `choices`, wrapping around at either end.
+fn cycle<T: PartialEq + Copy>(choices: &[T], current: T, forward: bool) -> T {
+    let n = choices.len();
+    let i = choices.iter().position(|c| *c == current).unwrap_or(0);
+    if forward {
+        choices[(i + 1) % n]
+    } else {
+        choices[(i + n - 1) % n]
+    }
+}
+
+/// Changes one setting, applying it straight away where the game can.
+fn change_option(ecs: &mut World, option: usize, forward: bool) {
+    let mut settings = ecs.write_resource::<Settings>();
+    match option {
+        0 => {
+            settings.font = match settings.font {
+                Font::Terminal8x8 => Font::Vga8x16,
+                Font::Vga8x16 => Font::Terminal8x8,
+            }
+        }
+        1 => {
+            settings.graphics = match settings.graphics {
+                Graphics::Ascii => Graphics::Tiles,
+                Graphics::Tiles => Graphics::Ascii,
+            }
+        }
+        2 => {
+            settings.theme = cycle(THEMES, settings.theme, forward);
+            ecs.write_resource::<Palette>().theme = settings.theme;
+        }
+        3 => settings.scanlines = !settings.scanlines,
+        4 => settings.fullscreen = !settings.fullscreen,
+        5 => {
+            settings.log_length = cycle(LOG_LENGTHS, settings.log_length, forward);
+            ecs.write_resource::<gamelog::GameLog>()
+                .set_max_entries(settings.log_length);
+        }
+        6 => settings.autosave_turns = cycle(AUTOSAVE_TURNS, settings.autosave_turns, forward),
+        7 => {
+            settings.autoexplore_pickup = !settings.autoexplore_pickup;
+            ecs.write_resource::<AutoMove>().pickup_items = settings.autoexplore_pickup;
+        }
+        8 => {
+            // Speech may be on for just this run, from the command line
+            settings.screen_reader = !ecs.has_value::<speech::Speech>();
+            settings.save();
+            let (on, output) = (
+                settings.screen_reader,
+                settings.screen_reader_output.clone(),
+            );
+            drop(settings);
+            speech::enable(ecs, on, &output);
+            return;
+        }
+        _ => {
+            let mut bindings = ecs.write_resource::<KeyBindings>();
+            let preset = cycle(
+                &[Preset::Vi, Preset::Numpad, Preset::Wasd],
+                bindings.preset,
+                forward,
+            );
+            bindings.set_preset(preset);
+            bindings.save();
+            return;
+        }
+    }
+    settings.save();
+}
+
+/// Each option's label and current value, as the options screen lists them.
+pub fn option_rows(ecs: &World) -> [(&'static str, String); OPTION_COUNT] {
+    let settings = ecs.fetch::<Settings>();
+    let bindings = ecs.fetch::<KeyBindings>();
+    let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+    [
+        (
+            "Font *",
+            match settings.font {
+                Font::Terminal8x8 => "Terminal 8x8".to_string(),
+                Font::Vga8x16 => "VGA 8x16".to_string(),
+            },
+        ),
+        (
+            "Graphics *",
+            match settings.graphics {
+                Graphics::Ascii => "ASCII".to_string(),
+                Graphics::Tiles => "Tiles".to_string(),
+            },
+        ),
+        ("Colours", settings.theme.label().to_string()),
+        ("Scanlines *", on_off(settings.scanlines)),
+        ("Fullscreen *", on_off(settings.fullscreen)),
+        ("Messages kept", settings.log_length.to_string()),
+        (
+            "Autosave",
+            if settings.autosave_turns > 0 {
+                format!("Every {} turns", settings.autosave_turns)
+            } else {
+                "Off".to_string()
+            },
+        ),
+        (
+            "Autoexplore picks up items",
+            on_off(settings.autoexplore_pickup),
+        ),
+        ("Screen reader", on_off(ecs.has_value::<speech::Speech>())),
+        ("Keys", bindings.preset.label().to_string()),
+    ]
+}
+
+/// Lets the player change their settings, which are saved as they go.
+pub fn options(ecs: &mut World, ctx: &mut Rltk, selected: usize) -> OptionsResult {
+    let rows = option_rows(ecs);
+    {
+        let palette = ecs.fetch::<Palette>();
+
+        tileset::clear_screen(ctx);
+        ctx.draw_box_double(
+            14,
+            10,
+            51,
+            OPTION_COUNT as i32 + 8,
+            palette.get(Colour::Border),
+            palette.get(Colour::Background),
+        );
+        ctx.print_color_centered(
+            12,
+            palette.get(Colour::Title),
+            palette.get(Colour::Background),
+            "Options",
+        );
+        for (i, (label, value)) in rows.iter().enumerate() {
+            let y = 14 + i as i32;
+            let fg = if i == selected {
+                palette.get(Colour::Selected)
+            } else {
+                palette.get(Colour::Text)
+            };
+            ctx.print_color(17, y, fg, palette.get(Colour::Background), label);
+            ctx.print_color(47, y, fg, palette.get(Colour::Background), value);
+        }
+        ctx.print_color_centered(
+            OPTION_COUNT as i32 + 15,
+            palette.get(Colour::Muted),
+            palette.get(Colour::Background),
+            "* takes effect after a restart",
+        );
+        ctx.print_color_centered(
+            OPTION_COUNT as i32 + 16,
+            palette.get(Colour::Muted),
+            palette.get(Colour::Background),
+            "Up/Down to choose, Left/Right to change, ESCAPE to return",
+        );
+    }
+
+    match ctx.key {
+        None => OptionsResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Escape => OptionsResult::Close,
+            VirtualKeyCode::Up => OptionsResult::Selected {
+                selected: (selected + OPTION_COUNT - 1) % OPTION_COUNT,
+            },
+            VirtualKeyCode::Down => OptionsResult::Selected {
+                selected: (selected + 1) % OPTION_COUNT,
+            },
+            VirtualKeyCode::Left => {
+                change_option(ecs, selected, false);
+                OptionsResult::NoResponse
+            }
+            VirtualKeyCode::Right | VirtualKeyCode::Return | VirtualKeyCode::Space => {
+                change_option(ecs, selected, true);
+                OptionsResult::NoResponse
+            }
+            _ => OptionsResult::NoResponse,
+        },
+    }
+}
@@ -1,9 +1,21 @@
-use super::{Confusion, EntityMoved, Map, Monster, Position, RunState, Viewshed, WantsToMelee};
-use rltk::Point;
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    map::TileType,
+    palette::Colour,
+    particle_system::ParticleBuilder,
+    spawner, AbilityEffect, CombatStats, Confusion, EntityMoved, Map, Monster, MonsterAbilities,
+    Name, Position, RunState, Viewshed, WantsToCastSpell, WantsToMelee, WantsToSummon,
+    WantsToUseItem,
+};
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
 pub struct MonsterAI {}
 
+fn hp_percent(stats: &CombatStats) -> i32 {
+    stats.hp * 100 / i32::max(1, stats.max_hp)
+}
+
 impl<'a> System<'a> for MonsterAI {
     #[allow(clippy::type_complexity)]
     type SystemData = (
@@ -18,6 +30,15 @@ impl<'a> System<'a> for MonsterAI {
         WriteStorage<'a, WantsToMelee>,
         WriteStorage<'a, Confusion>,
         WriteStorage<'a, EntityMoved>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, MonsterAbilities>,
+        WriteStorage<'a, WantsToCastSpell>,
+        WriteStorage<'a, WantsToSummon>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, ParticleBuilder>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, WantsToUseItem>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -33,15 +54,43 @@ impl<'a> System<'a> for MonsterAI {
             mut wants_to_melee,
             mut confused,
             mut entity_moved,
+            combat_stats,
+            mut abilities,
+            mut wants_to_cast,
+            mut wants_to_summon,
+            mut rng,
+            mut particle_builder,
+            mut gamelog,
+            names,
+            wants_use,
         ) = data;
 
         if *runstate != RunState::MonsterTurn {
             return;
         }
 
+        // Where every monster is, so support abilities can find wounded allies
+        let allies: Vec<(Point, i32)> = (&monster, &position, &combat_stats)
+            .join()
+            .map(|(_, pos, stats)| (Point::new(pos.x, pos.y), hp_percent(stats)))
+            .collect();
+
         for (entity, mut viewshed, _monster, mut pos) in
             (&entities, &mut viewshed, &monster, &mut position).join()
         {
+            if let Some(abilities) = abilities.get_mut(entity) {
+                for ability in abilities.abilities.iter_mut() {
+                    if ability.cooldown_remaining > 0 {
+                        ability.cooldown_remaining -= 1;
+                    }
+                }
+            }
+
+            // Using an item takes the whole turn
+            if wants_use.contains(entity) {
+                continue;
+            }
+
             let mut can_act = true;
             let is_confused = confused.get_mut(entity);
             if let Some(i_am_confused) = is_confused {
@@ -51,38 +100,153 @@ impl<'a> System<'a> for MonsterAI {
                 }
                 can_act = false;
             }
-            if can_act {
-                let distance =
-                    rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
-                if distance < 1.5 {
-                    wants_to_melee
-                        .insert(
-                            entity,
-                            WantsToMelee {
-                                target: *player_entity,
-                            },
-                        )
-                        .expect("Unable to insert attack");
-                } else if viewshed.visible_tiles.contains(&*player_pos) {
-                    // Path to the player
-                    let path = rltk::a_star_search(
-                        map.xy_idx(pos.x, pos.y),
-                        map.xy_idx(player_pos.x, player_pos.y),
-                        &mut *map,
-                    );
-                    if path.success && path.steps.len() > 1 {
-                        let mut idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked_tiles[idx] = false;
-                        pos.x = path.steps[1] as i32 % map.width;
-                        pos.y = path.steps[1] as i32 / map.width;
-                        entity_moved
-                            .insert(entity, EntityMoved {})
-                            .expect("Unable to insert marker");
-                        idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked_tiles[idx] = true;
-                        viewshed.dirty = true;
+            if !can_act {
+                continue;
+            }
+
+            let my_pos = Point::new(pos.x, pos.y);
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *player_pos);
+            let sees_player = viewshed.visible_tiles.contains(&*player_pos);
+
+            // Special abilities take priority over walking and hitting, in the order given
+            if sees_player {
+                let my_hp = combat_stats.get(entity).map_or(100, hp_percent);
+                let mut used: Option<(AbilityEffect, Point)> = None;
+                if let Some(abilities) = abilities.get_mut(entity) {
+                    for ability in abilities.abilities.iter_mut() {
+                        if ability.cooldown_remaining > 0 {
+                            continue;
+                        }
+                        // Never close enough to be caught in its own blast
+                        let blast = match &ability.effect {
+                            AbilityEffect::CastSpell { spell } => {
+                                spawner::spell_blast_radius(spell)
+                            }
+                            _ => None,
+                        };
+                        let in_range = |target: Point| {
+                            let d = rltk::DistanceAlg::Pythagoras.distance2d(my_pos, target);
+                            d >= ability.min_range
+                                && d <= ability.max_range
+                                && !blast.is_some_and(|radius| d <= radius as f32)
+                        };
+                        let target = match &ability.effect {
+                            AbilityEffect::SupportSpell { .. } => allies
+                                .iter()
+                                .filter(|(p, hp)| {
+                                    *hp <= ability.hp_threshold
+                                        && viewshed.visible_tiles.contains(p)
+                                        && in_range(*p)
+                                })
+                                .min_by_key(|(_, hp)| *hp)
+                                .map(|(p, _)| *p),
+                            _ if my_hp <= ability.hp_threshold && in_range(*player_pos) => {
+                                Some(*player_pos)
+                            }
+                            _ => None,
+                        };
+                        if let Some(target) = target {
+                            ability.cooldown_remaining = ability.cooldown;
+                            used = Some((ability.effect.clone(), target));
+                            break;
+                        }
                     }
                 }
+
+                if let Some((effect, target)) = used {
+                    match effect {
+                        AbilityEffect::CastSpell { spell }
+                        | AbilityEffect::SupportSpell { spell } => {
+                            wants_to_cast
+                                .insert(
+                                    entity,
+                                    WantsToCastSpell {
+                                        spell,
+                                        target: Some(target),
+                                    },
+                                )
+                                .expect("Unable to insert cast");
+                        }
+                        AbilityEffect::Summon { minion, count } => {
+                            wants_to_summon
+                                .insert(entity, WantsToSummon { minion, count })
+                                .expect("Unable to insert summon");
+                        }
+                        AbilityEffect::Teleport => {
+                            // Somewhere open, and well out of the player's reach
+                            for _try in 0..50 {
+                                let x = rng.roll_dice(1, map.width - 2);
+                                let y = rng.roll_dice(1, map.height - 2);
+                                let idx = map.xy_idx(x, y);
+                                let far_enough = rltk::DistanceAlg::Pythagoras
+                                    .distance2d(Point::new(x, y), *player_pos)
+                                    > 8.0;
+                                if map.tiles[idx] == TileType::Floor
+                                    && !map.blocked_tiles[idx]
+                                    && far_enough
+                                {
+                                    particle_builder.request(
+                                        pos.x,
+                                        pos.y,
+                                        Colour::Magic,
+                                        rltk::RGB::named(rltk::BLACK),
+                                        rltk::to_cp437('*'),
+                                        200.0,
+                                    );
+                                    if let Some(name) = names.get(entity) {
+                                        gamelog.add(
+                                            LogMessage::new(LogCategory::Combat)
+                                                .text("The ")
+                                                .npc(&name.name)
+                                                .text(" vanishes!"),
+                                        );
+                                    }
+                                    let old_idx = map.xy_idx(pos.x, pos.y);
+                                    map.blocked_tiles[old_idx] = false;
+                                    pos.x = x;
+                                    pos.y = y;
+                                    map.blocked_tiles[idx] = true;
+                                    entity_moved
+                                        .insert(entity, EntityMoved {})
+                                        .expect("Unable to insert marker");
+                                    viewshed.dirty = true;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            if distance < 1.5 {
+                wants_to_melee
+                    .insert(
+                        entity,
+                        WantsToMelee {
+                            target: *player_entity,
+                        },
+                    )
+                    .expect("Unable to insert attack");
+            } else if sees_player {
+                // Path to the player
+                let path = rltk::a_star_search(
+                    map.xy_idx(pos.x, pos.y),
+                    map.xy_idx(player_pos.x, player_pos.y),
+                    &mut *map,
+                );
+                if path.success && path.steps.len() > 1 {
+                    let mut idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked_tiles[idx] = false;
+                    pos.x = path.steps[1] as i32 % map.width;
+                    pos.y = path.steps[1] as i32 / map.width;
+                    entity_moved
+                        .insert(entity, EntityMoved {})
+                        .expect("Unable to insert marker");
+                    idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked_tiles[idx] = true;
+                    viewshed.dirty = true;
+                }
             }
         }
     }
@@ -0,0 +1,194 @@
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    AreaOfEffect, CarriesItems, CombatStats, Confusion, Equippable, Equipped, Gold, InBackpack,
+    InflictsDamage, Item, Map, Name, Position, ProvidesHealing, Ranged, RunState, Viewshed,
+    WantsToPickupItem, WantsToUseItem,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+/// What a monster decides to do with one of its items this turn.
+enum ItemChoice {
+    Drink(Entity),
+    ReadAt(Entity, Point),
+    Wield(Entity),
+}
+
+/// The item a monster would use now, if any: a healing potion when badly hurt, then a
+/// damaging scroll at the player, then a weapon or shield for an empty hand.
+fn choose(ecs: &World, monster: Entity, pos: Point, sees_player: bool) -> Option<ItemChoice> {
+    let player_pos = *ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let equippable = ecs.read_storage::<Equippable>();
+    let healing = ecs.read_storage::<ProvidesHealing>();
+    let ranged = ecs.read_storage::<Ranged>();
+    let damages = ecs.read_storage::<InflictsDamage>();
+    let areas = ecs.read_storage::<AreaOfEffect>();
+
+    let carried: Vec<Entity> = (&entities, &backpack)
+        .join()
+        .filter(|(_, pack)| pack.owner == monster)
+        .map(|(item, _)| item)
+        .collect();
+
+    let hurt = ecs
+        .read_storage::<CombatStats>()
+        .get(monster)
+        .is_some_and(|stats| stats.hp * 2 < stats.max_hp);
+    if hurt {
+        if let Some(potion) = carried
+            .iter()
+            .find(|item| healing.contains(**item) && !ranged.contains(**item))
+        {
+            return Some(ItemChoice::Drink(*potion));
+        }
+    }
+
+    if sees_player {
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(pos, player_pos);
+        // Never close enough to be caught in its own blast
+        let scroll = carried.iter().find(|item| {
+            let too_close = distance < 1.5
+                || areas
+                    .get(**item)
+                    .is_some_and(|area| distance <= area.radius as f32);
+            ranged
+                .get(**item)
+                .is_some_and(|r| distance <= r.range as f32 && !too_close)
+                && damages.contains(**item)
+        });
+        if let Some(scroll) = scroll {
+            return Some(ItemChoice::ReadAt(*scroll, player_pos));
+        }
+    }
+
+    carried
+        .iter()
+        .find(|item| {
+            equippable.get(**item).is_some_and(|can_equip| {
+                !equipped
+                    .join()
+                    .any(|worn| worn.owner == monster && worn.slot == can_equip.slot)
+            })
+        })
+        .map(|item| ItemChoice::Wield(*item))
+}
+
+/// Lets monsters that carry items use them: drinking, reading, or arming themselves. A monster
+/// using an item does nothing else that turn.
+pub fn use_items(ecs: &mut World) {
+    if *ecs.fetch::<RunState>() != RunState::MonsterTurn {
+        return;
+    }
+    let player_pos = *ecs.fetch::<Point>();
+    let users: Vec<(Entity, Point, bool)> = (
+        &ecs.entities(),
+        &ecs.read_storage::<CarriesItems>(),
+        &ecs.read_storage::<Position>(),
+        &ecs.read_storage::<Viewshed>(),
+        !&ecs.read_storage::<Confusion>(),
+    )
+        .join()
+        .map(|(entity, _, pos, viewshed, _)| {
+            (
+                entity,
+                Point::new(pos.x, pos.y),
+                viewshed.visible_tiles.contains(&player_pos),
+            )
+        })
+        .collect();
+
+    for (monster, pos, sees_player) in users {
+        let (item, target, verb) = match choose(ecs, monster, pos, sees_player) {
+            None => continue,
+            Some(ItemChoice::Drink(item)) => (item, None, "drinks"),
+            Some(ItemChoice::ReadAt(item, target)) => (item, Some(target), "reads"),
+            Some(ItemChoice::Wield(item)) => (item, None, "readies"),
+        };
+
+        {
+            let map = ecs.fetch::<Map>();
+            let names = ecs.read_storage::<Name>();
+            if let (Some(name), Some(item_name)) = (names.get(monster), names.get(item)) {
+                if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                    ecs.write_resource::<GameLog>().add(
+                        LogMessage::new(LogCategory::Combat)
+                            .text("The ")
+                            .npc(&name.name)
+                            .text(format!(" {} a ", verb))
+                            .item(&item_name.name)
+                            .text("!"),
+                    );
+                }
+            }
+        }
+        ecs.write_storage::<WantsToUseItem>()
+            .insert(monster, WantsToUseItem { item, target })
+            .expect("Unable to insert intent");
+    }
+}
+
+/// Whether a monster has any use for an item: something to wield, drink or read at the
+/// player, or gold.
+fn wanted(ecs: &World, item: Entity) -> bool {
+    ecs.read_storage::<Equippable>().contains(item)
+        || ecs.read_storage::<ProvidesHealing>().contains(item)
+        || ecs.read_storage::<InflictsDamage>().contains(item)
+        || ecs.read_storage::<Gold>().contains(item)
+}
+
+/// Monsters that carry items pick up anything useful they end their move standing on.
+pub fn pick_up_items(ecs: &mut World) {
+    if *ecs.fetch::<RunState>() != RunState::MonsterTurn {
+        return;
+    }
+    let pickups: Vec<(Entity, Entity, Point)> = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let items = ecs.read_storage::<Item>();
+        let carriers = ecs.read_storage::<CarriesItems>();
+        let wants_use = ecs.read_storage::<WantsToUseItem>();
+        (&entities, &carriers, &positions, !&wants_use)
+            .join()
+            .filter_map(|(monster, _, pos, _)| {
+                (&entities, &items, &positions)
+                    .join()
+                    .find(|(item, _, item_pos)| {
+                        item_pos.x == pos.x && item_pos.y == pos.y && wanted(ecs, *item)
+                    })
+                    .map(|(item, _, _)| (monster, item, Point::new(pos.x, pos.y)))
+            })
+            .collect()
+    };
+
+    for (monster, item, pos) in pickups {
+        {
+            let map = ecs.fetch::<Map>();
+            let names = ecs.read_storage::<Name>();
+            if let (Some(name), Some(item_name)) = (names.get(monster), names.get(item)) {
+                if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                    ecs.write_resource::<GameLog>().add(
+                        LogMessage::new(LogCategory::Item)
+                            .text("The ")
+                            .npc(&name.name)
+                            .text(" picks up the ")
+                            .item(&item_name.name)
+                            .text("."),
+                    );
+                }
+            }
+        }
+        ecs.write_storage::<WantsToPickupItem>()
+            .insert(
+                monster,
+                WantsToPickupItem {
+                    collected_by: monster,
+                    item,
+                    count: 1,
+                },
+            )
+            .expect("Unable to insert want to pickup");
+    }
+}
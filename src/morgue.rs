@@ -0,0 +1,216 @@
+use super::{
+    gamelog::GameLog, map::Map, CombatStats, DefenseBonus, Equipped, HungerClock, HungerState,
+    InBackpack, KnownSpells, Mana, MeleePowerBonus, Name, Purse,
+};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many of the most recent log messages end up in the morgue file.
+const MORGUE_LOG_LINES: usize = 15;
+
+/// What happened during the current run. Saved along with the game.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStatistics {
+    pub turns: i32,
+    pub deepest_depth: i32,
+    pub kills: BTreeMap<String, i32>,
+    pub items_found: BTreeMap<String, i32>,
+    pub items_used: BTreeMap<String, i32>,
+    pub spells_cast: BTreeMap<String, i32>,
+    /// The last thing to hurt the player, which is what killed them if they died.
+    pub last_hurt_by: Option<String>,
+    #[serde(default)]
+    pub xp: i32,
+    /// What the world's random number generator was seeded with at the start of the run.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub damage_dealt: i32,
+    #[serde(default)]
+    pub damage_taken: i32,
+    #[serde(default)]
+    pub potions_drunk: i32,
+    #[serde(default)]
+    pub traps_triggered: i32,
+    #[serde(default)]
+    pub tiles_revealed: i32,
+    #[serde(default)]
+    pub steps_walked: i32,
+}
+
+pub fn tally(counts: &mut BTreeMap<String, i32>, name: &str) {
+    *counts.entry(name.to_string()).or_insert(0) += 1;
+}
+
+/// The report for the run that just ended, kept for the game over and victory screens.
+#[derive(Default)]
+pub struct Morgue {
+    pub lines: Vec<String>,
+    /// Where the report was written, if writing it worked.
+    pub file: Option<String>,
+}
+
+fn section(lines: &mut Vec<String>, title: &str) {
+    lines.push(String::new());
+    lines.push(title.to_string());
+    lines.push("-".repeat(title.len()));
+}
+
+fn counts(lines: &mut Vec<String>, counts: &BTreeMap<String, i32>) {
+    if counts.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for (name, count) in counts.iter() {
+        lines.push(format!("  {:<30} {:>4}", name, count));
+    }
+}
+
+/// Builds the end-of-run report from the world as it is right now.
+pub fn build_report(ecs: &World, won: bool) -> Vec<String> {
+    let stats = ecs.fetch::<RunStatistics>();
+    let map = ecs.fetch::<Map>();
+    let log = ecs.fetch::<GameLog>();
+    let player_entity = ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let power_bonuses = ecs.read_storage::<MeleePowerBonus>();
+    let defense_bonuses = ecs.read_storage::<DefenseBonus>();
+
+    let mut lines = vec!["Run summary".to_string(), "===========".to_string()];
+    if won {
+        lines.push("Escaped the dungeon with the Amulet of the Ancients!".to_string());
+    } else {
+        lines.push(format!(
+            "Died on {}.",
+            if map.depth == super::map_builder::TOWN_DEPTH {
+                "the surface".to_string()
+            } else {
+                format!("depth {}", map.depth)
+            }
+        ));
+        lines.push(format!(
+            "Cause of death: {}",
+            stats.last_hurt_by.as_deref().unwrap_or("unknown")
+        ));
+    }
+    lines.push(format!("Deepest depth reached: {}", stats.deepest_depth));
+    lines.push(format!("Turns taken: {}", stats.turns));
+    lines.push(format!("Experience: {}", stats.xp));
+    lines.push(format!(
+        "Damage dealt: {}  Damage taken: {}",
+        stats.damage_dealt, stats.damage_taken
+    ));
+    lines.push(format!(
+        "Steps walked: {}  Tiles revealed: {}",
+        stats.steps_walked, stats.tiles_revealed
+    ));
+    lines.push(format!(
+        "Potions drunk: {}  Traps triggered: {}",
+        stats.potions_drunk, stats.traps_triggered
+    ));
+    lines.push(format!("Seed: {}", stats.seed));
+
+    section(&mut lines, "Final stats");
+    if let Some(cs) = combat_stats.get(*player_entity) {
+        let mut power = cs.power;
+        let mut defense = cs.defense;
+        for (bonus, item) in (&power_bonuses, &equipped).join() {
+            if item.owner == *player_entity {
+                power += bonus.power;
+            }
+        }
+        for (bonus, item) in (&defense_bonuses, &equipped).join() {
+            if item.owner == *player_entity {
+                defense += bonus.defense;
+            }
+        }
+        lines.push(format!("  HP: {} / {}", cs.hp, cs.max_hp));
+        lines.push(format!("  Power: {}  Defense: {}", power, defense));
+    }
+    if let Some(mana) = ecs.read_storage::<Mana>().get(*player_entity) {
+        lines.push(format!("  Mana: {} / {}", mana.current, mana.max));
+    }
+    if let Some(purse) = ecs.read_storage::<Purse>().get(*player_entity) {
+        lines.push(format!("  Gold: {}", purse.gold));
+    }
+    if let Some(clock) = ecs.read_storage::<HungerClock>().get(*player_entity) {
+        let hunger = match clock.state {
+            HungerState::WellFed => "Well fed",
+            HungerState::Normal => "Normal",
+            HungerState::Hungry => "Hungry",
+            HungerState::Starving => "Starving",
+        };
+        lines.push(format!("  Hunger: {}", hunger));
+    }
+
+    section(&mut lines, "Equipment");
+    let mut any = false;
+    for (item, name) in (&equipped, &names).join() {
+        if item.owner == *player_entity {
+            lines.push(format!("  {}", name.name));
+            any = true;
+        }
+    }
+    if !any {
+        lines.push("  (none)".to_string());
+    }
+
+    section(&mut lines, "Backpack");
+    let mut carried: BTreeMap<String, i32> = BTreeMap::new();
+    for (item, name) in (&backpack, &names).join() {
+        if item.owner == *player_entity {
+            tally(&mut carried, &name.name);
+        }
+    }
+    counts(&mut lines, &carried);
+
+    section(&mut lines, "Known spells");
+    match ecs.read_storage::<KnownSpells>().get(*player_entity) {
+        Some(known) if !known.spells.is_empty() => {
+            for spell in known.spells.iter() {
+                lines.push(format!("  {}", spell));
+            }
+        }
+        _ => lines.push("  (none)".to_string()),
+    }
+
+    section(
+        &mut lines,
+        &format!("Kills ({} total)", stats.kills.values().sum::<i32>()),
+    );
+    counts(&mut lines, &stats.kills);
+    section(&mut lines, "Items found");
+    counts(&mut lines, &stats.items_found);
+    section(&mut lines, "Items used");
+    counts(&mut lines, &stats.items_used);
+    section(&mut lines, "Spells cast");
+    counts(&mut lines, &stats.spells_cast);
+
+    section(&mut lines, "Last messages");
+    let skip = log.entries().len().saturating_sub(MORGUE_LOG_LINES);
+    for entry in log.entries().skip(skip) {
+        lines.push(format!("  [{:>5}] {}", entry.turn, entry.plain_text()));
+    }
+
+    lines
+}
+
+/// Builds the report, writes it to `morgue/` and keeps it around for the end-of-run screen.
+pub fn write_morgue(ecs: &mut World, won: bool) {
+    let lines = build_report(ecs, won);
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = format!("./morgue/morgue-{}.txt", stamp);
+    let file = fs::create_dir_all("./morgue")
+        .and_then(|_| fs::write(&path, lines.join("\n") + "\n"))
+        .ok()
+        .map(|_| path);
+
+    ecs.insert(Morgue { lines, file });
+}
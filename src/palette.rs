@@ -0,0 +1,214 @@
+use super::{Bystander, Container, EntryTrigger, Item, Monster, Player, Renderable, Vendor};
+use rltk::RGB;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Theme {
+    Classic,
+    /// Avoids telling things apart by red against green, using orange, blue and yellow instead.
+    Deuteranopia,
+    /// As deuteranopia, but with brighter warm colours, since reds look dark.
+    Protanopia,
+    /// Bright, saturated colours on black, and dim remembered tiles.
+    HighContrast,
+}
+
+pub const THEMES: &[Theme] = &[
+    Theme::Classic,
+    Theme::Deuteranopia,
+    Theme::Protanopia,
+    Theme::HighContrast,
+];
+
+impl Theme {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Deuteranopia => "Deuteranopia",
+            Theme::Protanopia => "Protanopia",
+            Theme::HighContrast => "High contrast",
+        }
+    }
+}
+
+/// What a colour means, so each theme can choose how it looks.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Colour {
+    Text,
+    /// Monsters, traps, and anything else that hurts.
+    Danger,
+    Warning,
+    Good,
+    Friendly,
+    Item,
+    Player,
+    Magic,
+    Mana,
+    Gold,
+    Wall,
+    Floor,
+    Stairs,
+    /// Tiles seen before but out of sight now are greyed out, then tinted with this.
+    Visited,
+    Bloodstain,
+    Highlight,
+    /// Cells in reach of a targeted spell or item.
+    Range,
+    /// Behind all text and boxes.
+    Background,
+    /// Hints, column headings and other quiet text.
+    Muted,
+    /// Things not available yet, like locked achievements.
+    Disabled,
+    /// Titles of menus and panels.
+    Title,
+    /// The frames around full-screen menus.
+    Border,
+    /// The chosen menu entry, and the mouse cursor.
+    Selected,
+    /// What keys to press to leave a screen.
+    Prompt,
+}
+
+/// The theme in use, for turning colour meanings into colours.
+pub struct Palette {
+    pub theme: Theme,
+}
+
+impl Palette {
+    pub fn get(&self, colour: Colour) -> RGB {
+        use Colour::*;
+        match self.theme {
+            Theme::Classic => match colour {
+                Text => RGB::named(rltk::WHITE),
+                Danger => RGB::named(rltk::RED),
+                Warning => RGB::named(rltk::ORANGE),
+                Good => RGB::named(rltk::GREEN),
+                Friendly => RGB::named(rltk::GREEN),
+                Item => RGB::named(rltk::CYAN),
+                Player => RGB::named(rltk::YELLOW),
+                Magic => RGB::named(rltk::MAGENTA),
+                Mana => RGB::named(rltk::BLUE),
+                Gold => RGB::named(rltk::GOLD),
+                Wall => RGB::from_f32(0., 1.0, 0.),
+                Floor => RGB::from_f32(0.0, 0.5, 0.5),
+                Stairs => RGB::from_f32(0., 1.0, 1.0),
+                Visited => RGB::named(rltk::WHITE),
+                Bloodstain => RGB::from_f32(0.75, 0., 0.),
+                Highlight => RGB::named(rltk::CYAN),
+                Range => RGB::named(rltk::BLUE),
+                Background => RGB::named(rltk::BLACK),
+                Muted => RGB::named(rltk::GRAY),
+                Disabled => RGB::named(rltk::DARK_GRAY),
+                Title => RGB::named(rltk::YELLOW),
+                Border => RGB::named(rltk::WHEAT),
+                Selected => RGB::named(rltk::MAGENTA),
+                Prompt => RGB::named(rltk::MAGENTA),
+            },
+            Theme::Deuteranopia => match colour {
+                Text => RGB::named(rltk::WHITE),
+                Danger => RGB::from_f32(0.9, 0.6, 0.0),
+                Warning => RGB::from_f32(0.95, 0.9, 0.25),
+                Good => RGB::from_f32(0.35, 0.7, 0.9),
+                Friendly => RGB::from_f32(0.35, 0.7, 0.9),
+                Item => RGB::from_f32(0.95, 0.9, 0.25),
+                Player => RGB::named(rltk::WHITE),
+                Magic => RGB::from_f32(0.8, 0.6, 0.7),
+                Mana => RGB::from_f32(0.0, 0.45, 0.7),
+                Gold => RGB::from_f32(0.95, 0.9, 0.25),
+                Wall => RGB::from_f32(0.6, 0.6, 0.8),
+                Floor => RGB::from_f32(0.4, 0.4, 0.45),
+                Stairs => RGB::from_f32(0.8, 0.6, 0.7),
+                Visited => RGB::from_f32(0.8, 0.8, 0.9),
+                Bloodstain => RGB::from_f32(0.5, 0.3, 0.0),
+                Highlight => RGB::from_f32(0.35, 0.7, 0.9),
+                Range => RGB::from_f32(0.0, 0.3, 0.5),
+                Background => RGB::named(rltk::BLACK),
+                Muted => RGB::from_f32(0.6, 0.6, 0.65),
+                Disabled => RGB::from_f32(0.35, 0.35, 0.4),
+                Title => RGB::from_f32(0.95, 0.9, 0.25),
+                Border => RGB::from_f32(0.8, 0.8, 0.9),
+                Selected => RGB::from_f32(0.35, 0.7, 0.9),
+                Prompt => RGB::from_f32(0.8, 0.6, 0.7),
+            },
+            Theme::Protanopia => match colour {
+                Text => RGB::named(rltk::WHITE),
+                Danger => RGB::from_f32(0.95, 0.75, 0.1),
+                Warning => RGB::from_f32(0.95, 0.9, 0.5),
+                Good => RGB::from_f32(0.45, 0.55, 1.0),
+                Friendly => RGB::from_f32(0.45, 0.55, 1.0),
+                Item => RGB::from_f32(0.35, 0.85, 0.9),
+                Player => RGB::named(rltk::WHITE),
+                Magic => RGB::from_f32(0.8, 0.6, 0.9),
+                Mana => RGB::from_f32(0.3, 0.45, 0.9),
+                Gold => RGB::from_f32(0.95, 0.9, 0.25),
+                Wall => RGB::from_f32(0.6, 0.6, 0.8),
+                Floor => RGB::from_f32(0.4, 0.4, 0.45),
+                Stairs => RGB::from_f32(0.8, 0.6, 0.9),
+                Visited => RGB::from_f32(0.8, 0.8, 0.9),
+                Bloodstain => RGB::from_f32(0.45, 0.35, 0.0),
+                Highlight => RGB::from_f32(0.35, 0.85, 0.9),
+                Range => RGB::from_f32(0.0, 0.3, 0.6),
+                Background => RGB::named(rltk::BLACK),
+                Muted => RGB::from_f32(0.6, 0.6, 0.65),
+                Disabled => RGB::from_f32(0.35, 0.35, 0.4),
+                Title => RGB::from_f32(0.95, 0.9, 0.25),
+                Border => RGB::from_f32(0.8, 0.8, 0.9),
+                Selected => RGB::from_f32(0.35, 0.85, 0.9),
+                Prompt => RGB::from_f32(0.8, 0.6, 0.9),
+            },
+            Theme::HighContrast => match colour {
+                Text => RGB::named(rltk::WHITE),
+                Danger => RGB::from_f32(1.0, 0.3, 0.3),
+                Warning => RGB::from_f32(1.0, 0.6, 0.0),
+                Good => RGB::from_f32(0.3, 1.0, 0.3),
+                Friendly => RGB::from_f32(0.3, 1.0, 0.3),
+                Item => RGB::from_f32(0.3, 1.0, 1.0),
+                Player => RGB::from_f32(1.0, 1.0, 0.0),
+                Magic => RGB::from_f32(1.0, 0.3, 1.0),
+                Mana => RGB::from_f32(0.4, 0.6, 1.0),
+                Gold => RGB::from_f32(1.0, 0.85, 0.0),
+                Wall => RGB::named(rltk::WHITE),
+                Floor => RGB::from_f32(0.6, 0.6, 0.6),
+                Stairs => RGB::from_f32(1.0, 0.3, 1.0),
+                Visited => RGB::from_f32(0.5, 0.5, 0.5),
+                Bloodstain => RGB::from_f32(0.6, 0., 0.),
+                Highlight => RGB::from_f32(1.0, 1.0, 0.0),
+                Range => RGB::from_f32(0.0, 0.0, 0.8),
+                Background => RGB::named(rltk::BLACK),
+                Muted => RGB::from_f32(0.8, 0.8, 0.8),
+                Disabled => RGB::from_f32(0.5, 0.5, 0.5),
+                Title => RGB::from_f32(1.0, 1.0, 0.0),
+                Border => RGB::named(rltk::WHITE),
+                Selected => RGB::from_f32(0.3, 1.0, 1.0),
+                Prompt => RGB::from_f32(1.0, 0.3, 1.0),
+            },
+        }
+    }
+
+    /// The colour to draw an entity in. The classic theme keeps each entity's own colour; the
+    /// others colour by what the entity is, so friend and foe are easy to tell apart.
+    pub fn entity_fg(&self, ecs: &World, entity: Entity, render: &Renderable) -> RGB {
+        if self.theme == Theme::Classic {
+            return render.fg;
+        }
+        if ecs.read_storage::<Player>().contains(entity) {
+            self.get(Colour::Player)
+        } else if ecs.read_storage::<Monster>().contains(entity)
+            || ecs.read_storage::<EntryTrigger>().contains(entity)
+        {
+            self.get(Colour::Danger)
+        } else if ecs.read_storage::<Bystander>().contains(entity)
+            || ecs.read_storage::<Vendor>().contains(entity)
+        {
+            self.get(Colour::Friendly)
+        } else if ecs.read_storage::<Item>().contains(entity)
+            || ecs.read_storage::<Container>().contains(entity)
+        {
+            self.get(Colour::Item)
+        } else {
+            render.fg
+        }
+    }
+}
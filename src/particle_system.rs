@@ -1,4 +1,7 @@
-use super::{ParticleLifetime, Position, Renderable, Rltk};
+use super::{
+    palette::{Colour, Palette},
+    ParticleLifetime, Position, Renderable, Rltk,
+};
 use rltk::RGB;
 use specs::prelude::*;
 
@@ -23,7 +26,7 @@ pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
 struct ParticleRequest {
     x: i32,
     y: i32,
-    fg: RGB,
+    fg: Colour,
     bg: RGB,
     glyph: rltk::FontCharType,
     lifetime: f32,
@@ -45,7 +48,7 @@ impl ParticleBuilder {
         &mut self,
         x: i32,
         y: i32,
-        fg: RGB,
+        fg: Colour,
         bg: RGB,
         glyph: rltk::FontCharType,
         lifetime: f32,
@@ -71,10 +74,18 @@ impl<'a> System<'a> for ParticleSpawnSystem {
         WriteStorage<'a, Renderable>,
         WriteStorage<'a, ParticleLifetime>,
         WriteExpect<'a, ParticleBuilder>,
+        ReadExpect<'a, Palette>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut positions, mut renderables, mut particles, mut particle_builder) = data;
+        let (
+            entities,
+            mut positions,
+            mut renderables,
+            mut particles,
+            mut particle_builder,
+            palette,
+        ) = data;
         for new_particle in particle_builder.requests.iter() {
             let p = entities.create();
             positions
@@ -90,7 +101,7 @@ impl<'a> System<'a> for ParticleSpawnSystem {
                 .insert(
                     p,
                     Renderable {
-                        fg: new_particle.fg,
+                        fg: palette.get(new_particle.fg),
                         bg: new_particle.bg,
                         glyph: new_particle.glyph,
                         render_order: 0,
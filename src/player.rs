@@ -1,5 +1,16 @@
-use super::{components::*, gamelog::GameLog, map::Map, map::TileType, RunState, State};
-use rltk::{Point, Rltk, VirtualKeyCode, RGB};
+use super::{
+    auto_move::{self, AutoMode},
+    components::*,
+    container,
+    gamelog::{GameLog, LogCategory},
+    gui::{self, InventorySort, VendorMode},
+    keybindings::{Action, KeyBindings},
+    map::Map,
+    map::TileType,
+    morgue::RunStatistics,
+    RunState, State,
+};
+use rltk::{Point, Rltk, RGB};
 use serde;
 use serde::{Deserialize, Serialize};
 use specs::{
@@ -14,7 +25,7 @@ use std::cmp::{max, min};
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Player {}
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState {
     let mut entity_moved = ecs.write_storage::<EntityMoved>();
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
@@ -23,6 +34,7 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let map = ecs.fetch::<Map>();
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let vendors = ecs.read_storage::<Vendor>();
 
     for (entity, _player, pos, viewshed) in
         (&entities, &mut players, &mut positions, &mut viewsheds).join()
@@ -32,10 +44,16 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             || pos.y + delta_y < 1
             || pos.y + delta_y > map.height - 1
         {
-            return;
+            return RunState::PlayerTurn;
         }
         let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
         for potential_target in map.tile_content[destination_idx].iter() {
+            if vendors.get(*potential_target).is_some() {
+                return RunState::ShowVendor {
+                    vendor: *potential_target,
+                    mode: VendorMode::Buy,
+                };
+            }
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
                 wants_to_melee
@@ -46,7 +64,7 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                         },
                     )
                     .expect("Add target failed");
-                return;
+                return RunState::PlayerTurn;
             }
         }
         if !map.blocked_tiles[destination_idx] {
@@ -63,96 +81,154 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             entity_moved
                 .insert(entity, EntityMoved {})
                 .expect("Unable to insert marker");
+            ecs.write_resource::<RunStatistics>().steps_walked += 1;
         }
     }
+    RunState::PlayerTurn
 }
 
-fn get_item(ecs: &mut World) {
-    let player_pos = ecs.fetch::<Point>();
-    let player_entity = ecs.fetch::<Entity>();
-    let entities = ecs.entities();
-    let items = ecs.read_storage::<Item>();
-    let positions = ecs.read_storage::<Position>();
-    let mut gamelog = ecs.fetch_mut::<GameLog>();
-
-    let mut target_item: Option<Entity> = None;
-    for (item_entity, _item, position) in (&entities, &items, &positions).join() {
-        if position.x == player_pos.x && position.y == player_pos.y {
-            target_item = Some(item_entity);
-        }
-    }
+/// Picks up what the player is standing on: straight away if it's a single stack, from a
+/// menu if there are several. With nothing to pick up, opens any chest here instead.
+fn get_item(ecs: &mut World) -> RunState {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let stacks = gui::stacks_at(ecs, player_pos.x, player_pos.y);
 
-    match target_item {
-        None => gamelog
-            .entries
-            .push("There is nothing here to pick up.".to_string()),
-        Some(item) => {
+    match stacks.len() {
+        0 => {
+            let chest = (
+                &ecs.entities(),
+                &ecs.read_storage::<Container>(),
+                &ecs.read_storage::<Position>(),
+            )
+                .join()
+                .find(|(_, _, pos)| pos.x == player_pos.x && pos.y == player_pos.y)
+                .map(|(entity, _, _)| entity);
+            match chest {
+                Some(chest) => return container::open(ecs, chest),
+                None => ecs
+                    .fetch_mut::<GameLog>()
+                    .push(LogCategory::System, "There is nothing here to pick up."),
+            }
+        }
+        1 => {
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
             pickup
                 .insert(
-                    *player_entity,
+                    player_entity,
                     WantsToPickupItem {
-                        collected_by: *player_entity,
-                        item,
+                        collected_by: player_entity,
+                        item: stacks[0].items[0],
+                        count: stacks[0].items.len() as i32,
                     },
                 )
                 .expect("Unable to insert want to pickup");
         }
+        _ => return RunState::ShowPickup { page: 0 },
     }
+    RunState::PlayerTurn
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    //Player Movement
-    match ctx.key {
+    // Clicking somewhere known walks there
+    let (x, y) = ctx.mouse_pos();
+    let on_map = {
+        let map = gs.ecs.fetch::<Map>();
+        x < map.width && y < map.height
+    };
+    if ctx.left_click && on_map {
+        return auto_move::start(
+            &mut gs.ecs,
+            AutoMode::Travel {
+                destination: Point::new(x, y),
+            },
+        );
+    }
+
+    let action = match ctx.key {
         None => return RunState::AwaitingInput, //Nothing happened
-        Some(key) => match key {
-            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs)
+        Some(key) => match gs.ecs.fetch::<KeyBindings>().action_for(key) {
+            None => return RunState::AwaitingInput,
+            Some(action) => action,
+        },
+    };
+    match action {
+        //Player Movement
+        Action::MoveWest => return try_move_player(-1, 0, &mut gs.ecs),
+        Action::MoveEast => return try_move_player(1, 0, &mut gs.ecs),
+        Action::MoveNorth => return try_move_player(0, -1, &mut gs.ecs),
+        Action::MoveSouth => return try_move_player(0, 1, &mut gs.ecs),
+
+        // Diagonals
+        Action::MoveNorthEast => return try_move_player(1, -1, &mut gs.ecs),
+        Action::MoveNorthWest => return try_move_player(-1, -1, &mut gs.ecs),
+        Action::MoveSouthEast => return try_move_player(1, 1, &mut gs.ecs),
+        Action::MoveSouthWest => return try_move_player(-1, 1, &mut gs.ecs),
+
+        Action::DropItem => {
+            return RunState::ShowDropItem {
+                page: 0,
+                sort: InventorySort::Category,
             }
-            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs)
+        }
+        Action::PickUp => return get_item(&mut gs.ecs),
+        Action::OpenInventory => {
+            return RunState::ShowInventory {
+                page: 0,
+                sort: InventorySort::Category,
             }
-            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs)
+        }
+        Action::RemoveItem => return RunState::ShowRemoveItem,
+        Action::CastSpell => return RunState::ShowSpells,
+        Action::Autoexplore => return auto_move::start(&mut gs.ecs, AutoMode::Explore),
+        Action::TravelToStairs => return auto_move::travel_to_stairs(&mut gs.ecs),
+        Action::Look => {
+            return RunState::LookMode {
+                cursor: *gs.ecs.fetch::<Point>(),
             }
-            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs)
+        }
+        Action::MessageLog => {
+            return RunState::ShowLog {
+                scroll: 0,
+                filter: None,
             }
+        }
+        Action::Help => return RunState::ShowHelp,
 
-            VirtualKeyCode::D => return RunState::ShowDropItem,
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
-            VirtualKeyCode::I => return RunState::ShowInventory,
-            VirtualKeyCode::R => return RunState::ShowRemoveItem,
-
-            // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => try_move_player(1, -1, &mut gs.ecs),
-
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => try_move_player(-1, -1, &mut gs.ecs),
-
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(1, 1, &mut gs.ecs),
-
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
-
-            // Level changes
-            VirtualKeyCode::Period => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
-                }
+        // Level changes
+        Action::Descend => {
+            if let Some(depth) = portal_here(&mut gs.ecs) {
+                return RunState::TakePortal { depth };
             }
+            if try_next_level(&mut gs.ecs) {
+                return RunState::NextLevel;
+            }
+        }
 
-            // Save and Quit
-            VirtualKeyCode::Escape => return RunState::SaveGame,
+        // Save and Quit
+        Action::SaveAndQuit => return RunState::SaveGame,
 
-            // Skip Turn
-            VirtualKeyCode::Numpad5 => return skip_turn(&mut gs.ecs),
-            VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
+        // Skip Turn
+        Action::Wait => return skip_turn(&mut gs.ecs),
 
-            _ => return RunState::AwaitingInput,
-        },
+        // Only used in menus
+        Action::Confirm | Action::Cancel => return RunState::AwaitingInput,
     }
     RunState::PlayerTurn
 }
 
+/// If the player is standing on a town portal, returns the depth it leads to.
+fn portal_here(ecs: &mut World) -> Option<i32> {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let portals = ecs.read_storage::<Portal>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    map.tile_content[player_idx]
+        .iter()
+        .find_map(|entity| portals.get(*entity))
+        .map(|portal| portal.depth)
+}
+
 pub fn try_next_level(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let map = ecs.fetch::<Map>();
@@ -161,9 +237,7 @@ pub fn try_next_level(ecs: &mut World) -> bool {
         true
     } else {
         let mut gamelog = ecs.fetch_mut::<GameLog>();
-        gamelog
-            .entries
-            .push("There is no way down from here.".to_string());
+        gamelog.push(LogCategory::System, "There is no way down from here.");
         false
     }
 }
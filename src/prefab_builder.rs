@@ -0,0 +1,230 @@
+use super::{
+    map::{Map, TileType, MAPHEIGHT, MAPWIDTH},
+    prefabs::{HorizontalPlacement, PrefabLevel, PrefabRoom, PrefabSection, VerticalPlacement},
+    rect::Rect,
+    Position,
+};
+use rltk::{rex::XpFile, RandomNumberGenerator};
+use std::collections::HashSet;
+
+/// Maps a template glyph onto the map, queueing any entity it stands for.
+/// Returns true if the glyph marks the player's starting position.
+fn char_to_map(ch: char, idx: usize, map: &mut Map, spawn_list: &mut Vec<(usize, String)>) -> bool {
+    let spawn = |name: &str, spawn_list: &mut Vec<(usize, String)>| {
+        spawn_list.push((idx, name.to_string()));
+    };
+
+    map.tiles[idx] = TileType::Floor;
+    match ch {
+        ' ' | '.' => {}
+        '#' => map.tiles[idx] = TileType::Wall,
+        '>' => map.tiles[idx] = TileType::DownStairs,
+        '@' => return true,
+        'g' => spawn("Goblin", spawn_list),
+        'o' => spawn("Orc", spawn_list),
+        '^' => spawn("Bear Trap", spawn_list),
+        '%' => spawn("Rations", spawn_list),
+        '!' => spawn("Health Potion", spawn_list),
+        '/' => spawn("Longsword", spawn_list),
+        '(' => spawn("Tower Shield", spawn_list),
+        '?' => spawn("Magic Mapping Scroll", spawn_list),
+        'B' => spawn("Blacksmith", spawn_list),
+        'A' => spawn("Alchemist", spawn_list),
+        'T' => spawn("Barkeep", spawn_list),
+        'p' => spawn("Townsperson", spawn_list),
+        'd' => spawn("Drunk", spawn_list),
+        'z' => spawn("Skeleton", spawn_list),
+        'L' => spawn("The Lich", spawn_list),
+        '"' => spawn("Amulet of the Ancients", spawn_list),
+        '0' => spawn("Portal Home", spawn_list),
+        _ => {
+            rltk::console::log(format!("Unknown glyph loading prefab: {}", ch));
+        }
+    }
+    false
+}
+
+/// Splits a text template into rows of exactly `width` x `height` characters.
+fn read_ascii_template(template: &str, width: usize, height: usize) -> Vec<Vec<char>> {
+    let body = template.strip_prefix('\n').unwrap_or(template);
+    let mut rows: Vec<Vec<char>> = body
+        .split('\n')
+        .take(height)
+        .map(|line| {
+            let mut row: Vec<char> = line.chars().take(width).collect();
+            row.resize(width, ' ');
+            row
+        })
+        .collect();
+    rows.resize(height, vec![' '; width]);
+    rows
+}
+
+/// Builds a whole level from an embedded REX Paint file. Only the first layer is read.
+/// Returns the map, the player's starting position and the entities to spawn.
+pub fn load_rex_level(
+    level: &PrefabLevel,
+    new_depth: i32,
+) -> (Map, Position, Vec<(usize, String)>) {
+    let mut map = Map::new(new_depth);
+    let mut spawn_list = Vec::new();
+    let mut start = Position {
+        x: MAPWIDTH as i32 / 2,
+        y: MAPHEIGHT as i32 / 2,
+    };
+
+    let xp_file = XpFile::from_resource(level.template).unwrap();
+    if let Some(layer) = xp_file.layers.first() {
+        for y in 0..usize::min(layer.height, MAPHEIGHT) {
+            for x in 0..usize::min(layer.width, MAPWIDTH) {
+                if let Some(cell) = layer.get(x, y) {
+                    let idx = map.xy_idx(x as i32, y as i32);
+                    // REX stores CP437 codes; our glyphs are all plain ASCII.
+                    let ch = (cell.ch as u8) as char;
+                    if char_to_map(ch, idx, &mut map, &mut spawn_list) {
+                        start = Position {
+                            x: x as i32,
+                            y: y as i32,
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    (map, start, spawn_list)
+}
+
+/// Returns true if every non-wall tile can be walked to from `start`.
+fn fully_connected(map: &mut Map, start: &Position) -> bool {
+    map.populate_blocked();
+    let start_idx = map.xy_idx(start.x, start.y);
+    let dijkstra = rltk::DijkstraMap::new(MAPWIDTH, MAPHEIGHT, &[start_idx], &*map, 1000.0);
+    map.tiles
+        .iter()
+        .enumerate()
+        .all(|(idx, tile)| *tile == TileType::Wall || dijkstra.map[idx] < f32::MAX)
+}
+
+/// Stamps a template at (x, y), keeping the result only if the level stays fully connected
+/// and neither the player's start nor the stairs were covered.
+fn try_stamp(
+    map: &mut Map,
+    rows: &[Vec<char>],
+    x: i32,
+    y: i32,
+    start: &Position,
+    spawn_list: &mut Vec<(usize, String)>,
+) -> bool {
+    let backup = map.tiles.clone();
+    let mut stamped_spawns = Vec::new();
+    let mut stamped: HashSet<usize> = HashSet::new();
+
+    for (ty, row) in rows.iter().enumerate() {
+        for (tx, ch) in row.iter().enumerate() {
+            let (mx, my) = (x + tx as i32, y + ty as i32);
+            if mx < 1 || mx > MAPWIDTH as i32 - 2 || my < 1 || my > MAPHEIGHT as i32 - 2 {
+                continue;
+            }
+            let idx = map.xy_idx(mx, my);
+            if (mx == start.x && my == start.y) || backup[idx] == TileType::DownStairs {
+                map.tiles = backup;
+                return false;
+            }
+            char_to_map(*ch, idx, map, &mut stamped_spawns);
+            stamped.insert(idx);
+        }
+    }
+
+    if !fully_connected(map, start) {
+        map.tiles = backup;
+        return false;
+    }
+
+    // The prefab replaces anything the generator had planned for that area
+    spawn_list.retain(|(idx, _)| !stamped.contains(idx));
+    spawn_list.append(&mut stamped_spawns);
+    true
+}
+
+/// Stamps a section into its anchored corner of the map, unless it would overlap the room the
+/// player starts in and put its monsters next to them. Rooms it overlaps are removed from
+/// `spawn_rooms` so the random spawner leaves the area to the prefab.
+pub fn apply_section(
+    map: &mut Map,
+    section: &PrefabSection,
+    start: &Position,
+    spawn_rooms: &mut Vec<Rect>,
+    spawn_list: &mut Vec<(usize, String)>,
+) -> bool {
+    let rows = read_ascii_template(section.template, section.width, section.height);
+    let (w, h) = (section.width as i32, section.height as i32);
+    let x = match section.placement.0 {
+        HorizontalPlacement::Left => 0,
+        HorizontalPlacement::Center => (MAPWIDTH as i32 / 2) - (w / 2),
+        HorizontalPlacement::Right => (MAPWIDTH as i32 - 1) - w,
+    };
+    let y = match section.placement.1 {
+        VerticalPlacement::Top => 0,
+        VerticalPlacement::Center => (MAPHEIGHT as i32 / 2) - (h / 2),
+        VerticalPlacement::Bottom => (MAPHEIGHT as i32 - 1) - h,
+    };
+
+    let area = Rect::new(x, y, w, h);
+    let start_room = map.rooms.iter().find(|room| {
+        start.x > room.x1 && start.x <= room.x2 && start.y > room.y1 && start.y <= room.y2
+    });
+    if start_room.is_some_and(|room| room.intersect(&area)) {
+        return false;
+    }
+    if !try_stamp(map, &rows, x, y, start, spawn_list) {
+        return false;
+    }
+    spawn_rooms.retain(|room| !room.intersect(&area));
+    true
+}
+
+/// Turns some of the generated rooms into vaults. A room becomes a vault if a template for
+/// this depth fits inside it; vault rooms are removed from `spawn_rooms`.
+pub fn apply_room_vaults(
+    map: &mut Map,
+    rng: &mut RandomNumberGenerator,
+    start: &Position,
+    spawn_rooms: &mut Vec<Rect>,
+    spawn_list: &mut Vec<(usize, String)>,
+    vaults: &[PrefabRoom],
+) {
+    let depth = map.depth;
+    let mut candidates: Vec<&PrefabRoom> = vaults
+        .iter()
+        .filter(|v| depth >= v.first_depth && depth <= v.last_depth)
+        .collect();
+
+    // Roughly one level in three gets a vault, and deeper levels may get a second one.
+    let n_vaults = i32::min(rng.roll_dice(1, 3) - 2 + depth / 4, 2);
+    let mut used_rooms: Vec<Rect> = Vec::new();
+    for _ in 0..n_vaults {
+        if candidates.is_empty() {
+            break;
+        }
+        let vault_index = (rng.roll_dice(1, candidates.len() as i32) - 1) as usize;
+        let vault = candidates[vault_index];
+        let rows = read_ascii_template(vault.template, vault.width, vault.height);
+
+        for room in spawn_rooms.iter() {
+            // Room interiors run from x1 + 1 ..= x2, see Map::apply_room_to_map
+            let (room_w, room_h) = (room.x2 - room.x1, room.y2 - room.y1);
+            if room_w < vault.width as i32 || room_h < vault.height as i32 {
+                continue;
+            }
+            let x = room.x1 + 1 + (room_w - vault.width as i32) / 2;
+            let y = room.y1 + 1 + (room_h - vault.height as i32) / 2;
+            if try_stamp(map, &rows, x, y, start, spawn_list) {
+                used_rooms.push(*room);
+                break;
+            }
+        }
+        spawn_rooms.retain(|room| !used_rooms.contains(room));
+        candidates.remove(vault_index);
+    }
+}
@@ -0,0 +1,208 @@
+//! Hand-authored level pieces, stamped into generated maps by `prefab_builder`.
+//!
+//! Templates are plain ASCII (or REX Paint layers for whole levels), one character per tile:
+//!
+//! | Glyph     | Meaning                  |
+//! |-----------|--------------------------|
+//! | ` ` `.`   | Floor                    |
+//! | `#`       | Wall                     |
+//! | `>`       | Down stairs              |
+//! | `@`       | Player start (floor)     |
+//! | `g`       | Goblin                   |
+//! | `o`       | Orc                      |
+//! | `^`       | Bear Trap                |
+//! | `%`       | Rations                  |
+//! | `!`       | Health Potion            |
+//! | `/`       | Longsword                |
+//! | `(`       | Tower Shield             |
+//! | `?`       | Magic Mapping Scroll     |
+//! | `B`       | Blacksmith (vendor)      |
+//! | `A`       | Alchemist (vendor)       |
+//! | `T`       | Barkeep (vendor)         |
+//! | `p`       | Townsperson              |
+//! | `d`       | Drunk                    |
+//! | `z`       | Skeleton                 |
+//! | `L`       | The Lich (boss)          |
+//! | `"`       | Amulet of the Ancients   |
+//! | `0`       | Portal back to town      |
+//!
+//! The newline right after the opening quote is ignored. Rows shorter than `width` (and missing
+//! rows at the bottom) are padded with floor, so trailing spaces don't need to be preserved.
+
+/// A whole level, loaded from an embedded REX Paint file.
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabLevel {
+    pub template: &'static str,
+    pub width: usize,
+    pub height: usize,
+}
+
+pub const ANCIENT_HALL: PrefabLevel = PrefabLevel {
+    template: "../../resources/prefabs/ancient_hall.xp",
+    width: 80,
+    height: 43,
+};
+
+/// The surface level every run starts in, and where Town Portal Scrolls lead.
+pub const TOWN: PrefabLevel = PrefabLevel {
+    template: "../../resources/prefabs/town.xp",
+    width: 80,
+    height: 43,
+};
+
+/// The final level: the Lich's arena, with the amulet in the sanctum behind it.
+pub const LICH_LAIR: PrefabLevel = PrefabLevel {
+    template: "../../resources/prefabs/lich_lair.xp",
+    width: 80,
+    height: 43,
+};
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum HorizontalPlacement {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VerticalPlacement {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// A large chunk of map stamped over an area of a generated level.
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabSection {
+    pub template: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub placement: (HorizontalPlacement, VerticalPlacement),
+    pub first_depth: i32,
+}
+
+pub const ORC_CAMP: PrefabSection = PrefabSection {
+    template: ORC_CAMP_TXT,
+    width: 20,
+    height: 11,
+    placement: (HorizontalPlacement::Right, VerticalPlacement::Bottom),
+    first_depth: 3,
+};
+
+const ORC_CAMP_TXT: &str = "
+
+ ##################
+ #   ^        ^   #
+ #  ###  oo  ###  #
+ #  #%#      #!#  #
+    # #  o   # #
+ #  #          #  #
+ #     o    o     #
+ #  ^    /      ^ #
+ ######      ######
+
+";
+
+pub const FORGOTTEN_SHRINE: PrefabSection = PrefabSection {
+    template: FORGOTTEN_SHRINE_TXT,
+    width: 15,
+    height: 8,
+    placement: (HorizontalPlacement::Center, VerticalPlacement::Top),
+    first_depth: 2,
+};
+
+const FORGOTTEN_SHRINE_TXT: &str = "
+###############
+#  ^       ^  #
+#  #########  #
+#  #!  ?  %#  #
+#  #   g   #  #
+#  ####.####  #
+#      ^
+
+";
+
+pub const GOBLIN_WARRENS: PrefabSection = PrefabSection {
+    template: GOBLIN_WARRENS_TXT,
+    width: 14,
+    height: 13,
+    placement: (HorizontalPlacement::Left, VerticalPlacement::Center),
+    first_depth: 1,
+};
+
+const GOBLIN_WARRENS_TXT: &str = "
+##############
+#g  #    #  g
+# % #  g #
+#   ## ###  #
+##   g    ###
+#  ###  #   #
+#    #  # g
+# g  #^ #   #
+###  #  ##  #
+#  g    %   #
+#  ###  #  g
+#        !
+##############
+";
+
+/// A hand-crafted room, stamped into the interior of a generated room.
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabRoom {
+    pub template: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub first_depth: i32,
+    pub last_depth: i32,
+}
+
+pub const TREASURE_ROOM: PrefabRoom = PrefabRoom {
+    template: TREASURE_ROOM_TXT,
+    width: 5,
+    height: 5,
+    first_depth: 1,
+    last_depth: 100,
+};
+
+const TREASURE_ROOM_TXT: &str = "
+#^ ^#
+^!/!^
+  ?
+^%(%^
+#^ ^#
+";
+
+pub const GOBLIN_DEN: PrefabRoom = PrefabRoom {
+    template: GOBLIN_DEN_TXT,
+    width: 6,
+    height: 5,
+    first_depth: 1,
+    last_depth: 4,
+};
+
+const GOBLIN_DEN_TXT: &str = "
+# gg #
+ g  g
+  %%
+ g  g
+# !! #
+";
+
+pub const ORC_ARENA: PrefabRoom = PrefabRoom {
+    template: ORC_ARENA_TXT,
+    width: 6,
+    height: 6,
+    first_depth: 3,
+    last_depth: 100,
+};
+
+const ORC_ARENA_TXT: &str = "
+#^  ^#
+^ oo ^
+  /(
+  !!
+^ oo ^
+#^  ^#
+";
+
+pub const ROOM_VAULTS: &[PrefabRoom] = &[TREASURE_ROOM, GOBLIN_DEN, ORC_ARENA];
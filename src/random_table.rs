@@ -1,57 +1,318 @@
 use rltk::RandomNumberGenerator;
 
+/// How likely an entry is, possibly varying with depth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weight {
+    /// The same weight at every depth.
+    Flat(i32),
+    /// `base + per_depth * depth`; entries whose weight drops to zero or below never appear.
+    Linear { base: i32, per_depth: i32 },
+    /// `weight` at `depth`, losing `falloff` for every level above or below it.
+    Peak {
+        depth: i32,
+        weight: i32,
+        falloff: i32,
+    },
+}
+
+impl Weight {
+    pub fn at(&self, depth: i32) -> i32 {
+        let w = match *self {
+            Weight::Flat(weight) => weight,
+            Weight::Linear { base, per_depth } => base + per_depth * depth,
+            Weight::Peak {
+                depth: peak,
+                weight,
+                falloff,
+            } => weight - falloff * i32::abs(depth - peak),
+        };
+        i32::max(0, w)
+    }
+}
+
+/// What rolling an entry produces: a spawn name, or a roll on another table.
+pub enum RandomResult {
+    Name(String),
+    Table(RandomTable),
+}
+
 pub struct RandomEntry {
-    name: String,
-    weight: i32,
+    result: RandomResult,
+    weight: Weight,
+    min_depth: i32,
+    max_depth: i32,
 }
 
 impl RandomEntry {
     pub fn new<S: ToString>(name: S, weight: i32) -> RandomEntry {
+        RandomEntry::with_result(RandomResult::Name(name.to_string()), Weight::Flat(weight))
+    }
+
+    fn with_result(result: RandomResult, weight: Weight) -> RandomEntry {
         RandomEntry {
-            name: name.to_string(),
+            result,
             weight,
+            min_depth: i32::MIN,
+            max_depth: i32::MAX,
+        }
+    }
+
+    /// The entry's weight at a depth, taking depth gating and empty sub-tables into account.
+    fn weight_at(&self, depth: i32) -> i32 {
+        if depth < self.min_depth || depth > self.max_depth {
+            return 0;
+        }
+        match &self.result {
+            RandomResult::Table(table) if table.total_weight_at(depth) == 0 => 0,
+            _ => self.weight.at(depth),
         }
     }
 }
 
-#[derive(Default)]
+/// A weighted list of spawn names. Tables are built for a depth (see `at_depth`), which sets
+/// the weight of depth-curved entries and which gated entries are eligible; nested tables are
+/// rolled at the depth of the table that contains them.
 pub struct RandomTable {
     entries: Vec<RandomEntry>,
-    total_weight: i32,
+    depth: i32,
+}
+
+impl Default for RandomTable {
+    fn default() -> Self {
+        RandomTable::new()
+    }
 }
 
 impl RandomTable {
     pub fn new() -> RandomTable {
         RandomTable {
             entries: Vec::new(),
-            total_weight: 0,
+            depth: 1,
         }
     }
 
+    /// Sets the depth this table is rolled at.
+    pub fn at_depth(mut self, depth: i32) -> RandomTable {
+        self.depth = depth;
+        self
+    }
+
     pub fn add<S: ToString>(mut self, name: S, weight: i32) -> RandomTable {
-        if weight > 0 {
-            self.total_weight += weight;
-            self.entries
-                .push(RandomEntry::new(name.to_string(), weight));
+        self.entries.push(RandomEntry::new(name, weight));
+        self
+    }
+
+    pub fn add_weighted<S: ToString>(mut self, name: S, weight: Weight) -> RandomTable {
+        self.entries.push(RandomEntry::with_result(
+            RandomResult::Name(name.to_string()),
+            weight,
+        ));
+        self
+    }
+
+    /// Adds a nested table, e.g. "any scroll", as a single entry of this one.
+    pub fn add_table(mut self, table: RandomTable, weight: Weight) -> RandomTable {
+        self.entries
+            .push(RandomEntry::with_result(RandomResult::Table(table), weight));
+        self
+    }
+
+    /// Restricts the most recently added entry to depths `min..=max`.
+    pub fn depths(mut self, min: i32, max: i32) -> RandomTable {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.min_depth = min;
+            entry.max_depth = max;
         }
         self
     }
 
+    fn total_weight_at(&self, depth: i32) -> i32 {
+        self.entries.iter().map(|e| e.weight_at(depth)).sum()
+    }
+
     pub fn roll(&self, rng: &mut RandomNumberGenerator) -> String {
-        if self.total_weight == 0 {
+        self.roll_at(self.depth, rng)
+    }
+
+    fn roll_at(&self, depth: i32, rng: &mut RandomNumberGenerator) -> String {
+        let total_weight = self.total_weight_at(depth);
+        if total_weight == 0 {
             return "None".to_string();
         }
-        let mut index: usize = 0;
 
-        while index < self.entries.len() {
-            let roll = rng.roll_dice(1, self.total_weight) - 1;
-            if roll < self.entries[index].weight {
-                return self.entries[index].name.clone();
+        // One roll, then walk the entries until the running total passes it
+        let mut roll = rng.roll_dice(1, total_weight) - 1;
+        for entry in self.entries.iter() {
+            let weight = entry.weight_at(depth);
+            if roll < weight {
+                return match &entry.result {
+                    RandomResult::Name(name) => name.clone(),
+                    RandomResult::Table(table) => table.roll_at(depth, rng),
+                };
             }
-
-            index += 1;
+            roll -= weight;
         }
 
         "None".to_string()
     }
+
+    /// Every name this table can produce at `depth`, with its probability of being rolled.
+    fn flatten(&self, depth: i32) -> Vec<(String, f64)> {
+        let total_weight = self.total_weight_at(depth) as f64;
+        let mut result: Vec<(String, f64)> = Vec::new();
+        if total_weight == 0.0 {
+            return result;
+        }
+
+        for entry in self.entries.iter() {
+            let chance = entry.weight_at(depth) as f64 / total_weight;
+            if chance == 0.0 {
+                continue;
+            }
+            let outcomes = match &entry.result {
+                RandomResult::Name(name) => vec![(name.clone(), 1.0)],
+                RandomResult::Table(table) => table.flatten(depth),
+            };
+            for (name, sub_chance) in outcomes {
+                match result.iter_mut().find(|(n, _)| *n == name) {
+                    Some(existing) => existing.1 += chance * sub_chance,
+                    None => result.push((name, chance * sub_chance)),
+                }
+            }
+        }
+        result
+    }
+
+    /// Rolls up to `n` different names, without replacement: each name is picked with the
+    /// table's probabilities among those not picked yet. Returns fewer than `n` if the table
+    /// runs out of names.
+    pub fn roll_distinct(&self, n: usize, rng: &mut RandomNumberGenerator) -> Vec<String> {
+        let mut outcomes = self.flatten(self.depth);
+        let mut picked = Vec::new();
+
+        while picked.len() < n && !outcomes.is_empty() {
+            let total: f64 = outcomes.iter().map(|(_, chance)| chance).sum();
+            let mut roll = rng.rand::<f64>() * total;
+            let mut index = outcomes.len() - 1;
+            for (i, (_, chance)) in outcomes.iter().enumerate() {
+                if roll < *chance {
+                    index = i;
+                    break;
+                }
+                roll -= chance;
+            }
+            picked.push(outcomes.remove(index).0);
+        }
+
+        picked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn frequencies(table: &RandomTable, rolls: i32) -> HashMap<String, f64> {
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let mut counts: HashMap<String, f64> = HashMap::new();
+        for _ in 0..rolls {
+            *counts.entry(table.roll(&mut rng)).or_insert(0.0) += 1.0;
+        }
+        for count in counts.values_mut() {
+            *count /= rolls as f64;
+        }
+        counts
+    }
+
+    fn assert_close(counts: &HashMap<String, f64>, name: &str, expected: f64) {
+        let actual = counts.get(name).copied().unwrap_or(0.0);
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "{}: expected {:.3}, got {:.3}",
+            name,
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn distribution_matches_weights() {
+        let table = RandomTable::new()
+            .add("Goblin", 10)
+            .add("Orc", 5)
+            .add("Health Potion", 3)
+            .add("Rations", 2);
+        let counts = frequencies(&table, 200_000);
+        assert_close(&counts, "Goblin", 0.5);
+        assert_close(&counts, "Orc", 0.25);
+        assert_close(&counts, "Health Potion", 0.15);
+        assert_close(&counts, "Rations", 0.1);
+    }
+
+    #[test]
+    fn nested_tables_split_their_weight() {
+        let scrolls = RandomTable::new()
+            .add("Fireball Scroll", 1)
+            .add("Confusion Scroll", 3);
+        let table = RandomTable::new()
+            .add("Goblin", 1)
+            .add_table(scrolls, Weight::Flat(1));
+        let counts = frequencies(&table, 200_000);
+        assert_close(&counts, "Goblin", 0.5);
+        assert_close(&counts, "Fireball Scroll", 0.125);
+        assert_close(&counts, "Confusion Scroll", 0.375);
+    }
+
+    #[test]
+    fn depth_gating_and_curves() {
+        let table = |depth| {
+            RandomTable::new()
+                .at_depth(depth)
+                .add("Goblin", 4)
+                .add_weighted(
+                    "Orc",
+                    Weight::Linear {
+                        base: 0,
+                        per_depth: 2,
+                    },
+                )
+                .add("Longsword", 4)
+                .depths(3, 5)
+        };
+        let shallow = frequencies(&table(1), 50_000);
+        assert_close(&shallow, "Goblin", 4.0 / 6.0);
+        assert_close(&shallow, "Longsword", 0.0);
+
+        let deep = frequencies(&table(4), 50_000);
+        assert_close(&deep, "Goblin", 4.0 / 16.0);
+        assert_close(&deep, "Orc", 8.0 / 16.0);
+        assert_close(&deep, "Longsword", 4.0 / 16.0);
+
+        assert_eq!(
+            Weight::Peak {
+                depth: 3,
+                weight: 10,
+                falloff: 4
+            }
+            .at(6),
+            0
+        );
+    }
+
+    #[test]
+    fn distinct_rolls_never_repeat() {
+        let table = RandomTable::new()
+            .add("Goblin", 50)
+            .add("Orc", 1)
+            .add("Rations", 1);
+        let mut rng = RandomNumberGenerator::seeded(7);
+        for _ in 0..100 {
+            let mut rolled = table.roll_distinct(3, &mut rng);
+            rolled.sort();
+            assert_eq!(rolled, vec!["Goblin", "Orc", "Rations"]);
+        }
+        assert_eq!(table.roll_distinct(5, &mut rng).len(), 3);
+        assert!(RandomTable::new().roll_distinct(2, &mut rng).is_empty());
+    }
 }
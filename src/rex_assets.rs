@@ -1,6 +1,9 @@
 use rltk::rex::XpFile;
 
 rltk::embedded_resource!(SMALL_DUNGEON, "../resources/SmallDungeon_80x50.xp");
+rltk::embedded_resource!(ANCIENT_HALL, "../resources/prefabs/ancient_hall.xp");
+rltk::embedded_resource!(TOWN, "../resources/prefabs/town.xp");
+rltk::embedded_resource!(LICH_LAIR, "../resources/prefabs/lich_lair.xp");
 
 pub struct RexAssets {
     pub menu: XpFile,
@@ -10,6 +13,10 @@ impl RexAssets {
     #[allow(clippy::new_without_default)]
     pub fn new() -> RexAssets {
         rltk::link_resource!(SMALL_DUNGEON, "../../resources/SmallDungeon_80x50.xp");
+        // Prefab levels are only linked here; prefab_builder loads them on demand.
+        rltk::link_resource!(ANCIENT_HALL, "../../resources/prefabs/ancient_hall.xp");
+        rltk::link_resource!(TOWN, "../../resources/prefabs/town.xp");
+        rltk::link_resource!(LICH_LAIR, "../../resources/prefabs/lich_lair.xp");
 
         RexAssets {
             menu: XpFile::from_resource("../../resources/SmallDungeon_80x50.xp").unwrap(),
@@ -25,9 +25,16 @@ macro_rules! serialize_individually {
 pub fn save_game(ecs: &mut World) {
     // Create helper
     let mapcopy = ecs.get_mut::<super::map::Map>().unwrap().clone();
+    let statscopy = ecs
+        .get_mut::<super::morgue::RunStatistics>()
+        .unwrap()
+        .clone();
     let savehelper = ecs
         .create_entity()
-        .with(SerializationHelper { map: mapcopy })
+        .with(SerializationHelper {
+            map: mapcopy,
+            stats: statscopy,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
@@ -78,7 +85,30 @@ pub fn save_game(ecs: &mut World) {
             Hidden,
             EntryTrigger,
             EntityMoved,
-            SingleActivation
+            SingleActivation,
+            Purse,
+            Gold,
+            ItemValue,
+            Vendor,
+            Bystander,
+            TownPortal,
+            Portal,
+            LootTable,
+            LeavesCorpse,
+            Mana,
+            KnownSpells,
+            TeachesSpell,
+            WantsToCastSpell,
+            MonsterAbilities,
+            WantsToSummon,
+            QuestItem,
+            ItemWeight,
+            Encumbrance,
+            Container,
+            InContainer,
+            Locked,
+            Trapped,
+            CarriesItems
         );
     }
 
@@ -165,7 +195,30 @@ pub fn load_game(ecs: &mut World) {
             Hidden,
             EntryTrigger,
             EntityMoved,
-            SingleActivation
+            SingleActivation,
+            Purse,
+            Gold,
+            ItemValue,
+            Vendor,
+            Bystander,
+            TownPortal,
+            Portal,
+            LootTable,
+            LeavesCorpse,
+            Mana,
+            KnownSpells,
+            TeachesSpell,
+            WantsToCastSpell,
+            MonsterAbilities,
+            WantsToSummon,
+            QuestItem,
+            ItemWeight,
+            Encumbrance,
+            Container,
+            InContainer,
+            Locked,
+            Trapped,
+            CarriesItems
         );
     }
 
@@ -179,6 +232,8 @@ pub fn load_game(ecs: &mut World) {
             let mut worldmap = ecs.write_resource::<super::map::Map>();
             *worldmap = h.map.clone();
             worldmap.tile_content = vec![Vec::new(); super::map::MAPCOUNT];
+            let mut stats = ecs.write_resource::<super::morgue::RunStatistics>();
+            *stats = h.stats.clone();
             deleteme = Some(e);
         }
         for (e, _p, pos) in (&entities, &player, &position).join() {
@@ -0,0 +1,122 @@
+use super::palette::Theme;
+use super::tileset::{Tileset, TEXT_LAYER};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const SETTINGS_FILE: &str = "./settings.json";
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Font {
+    /// Square 8x8 glyphs.
+    Terminal8x8,
+    /// Taller, easier to read 8x16 glyphs.
+    Vga8x16,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Graphics {
+    /// Everything drawn with font glyphs.
+    Ascii,
+    /// The map and anything with a tile drawn from the tileset in `resources/tileset.json`.
+    Tiles,
+}
+
+/// The player's preferences, kept in `settings.json` between runs. Anything missing from the
+/// file takes its default, so older files keep working.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub window_title: String,
+    pub font: Font,
+    pub graphics: Graphics,
+    pub theme: Theme,
+    pub scanlines: bool,
+    pub fullscreen: bool,
+    /// How many messages the log remembers.
+    pub log_length: usize,
+    /// Save the game every this many turns; zero turns autosaving off.
+    pub autosave_turns: i32,
+    /// Whether autoexplore walks over to items and picks them up.
+    pub autoexplore_pickup: bool,
+    /// Describe the game in plain text as well, for a screen reader.
+    pub screen_reader: bool,
+    /// Where that text goes: a file or named pipe, or stdout (stderr in the terminal build) if
+    /// empty.
+    pub screen_reader_output: String,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            window_title: "Wonderful RustMUD".to_string(),
+            font: Font::Terminal8x8,
+            graphics: Graphics::Ascii,
+            theme: Theme::Classic,
+            scanlines: false,
+            fullscreen: false,
+            log_length: 500,
+            autosave_turns: 0,
+            autoexplore_pickup: false,
+            screen_reader: false,
+            screen_reader_output: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings file, falling back to the defaults. Also returns why the file
+    /// couldn't be read, if it exists but is broken.
+    pub fn load() -> (Settings, Option<String>) {
+        match fs::read_to_string(SETTINGS_FILE) {
+            Err(_) => (Settings::default(), None),
+            Ok(data) => match serde_json::from_str(&data) {
+                Ok(settings) => (settings, None),
+                Err(e) => (
+                    Settings::default(),
+                    Some(format!("Could not read {}: {}", SETTINGS_FILE, e)),
+                ),
+            },
+        }
+    }
+
+    pub fn save(&self) {
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            if fs::write(SETTINGS_FILE, data).is_err() {
+                rltk::console::log("Unable to write the settings file");
+            }
+        }
+    }
+
+    /// Builds the window the way the settings ask for. Given a tileset, the window has a tile
+    /// layer under the text, with cells the size of the tiles.
+    pub fn build_context(&self, tileset: Option<&Tileset>) -> rltk::BResult<rltk::Rltk> {
+        let builder = match (tileset, self.font) {
+            (None, Font::Terminal8x8) => rltk::RltkBuilder::simple80x50(),
+            (None, Font::Vga8x16) => rltk::RltkBuilder::vga80x50(),
+            (Some(tileset), font) => {
+                let (text_font, width, height) = match font {
+                    Font::Terminal8x8 => ("terminal8x8.png", 8, 8),
+                    Font::Vga8x16 => ("vga8x16.png", 8, 16),
+                };
+                rltk::RltkBuilder::new()
+                    .with_dimensions(80, 50)
+                    .with_tile_dimensions(tileset.tile_width, tileset.tile_height)
+                    .with_font(&tileset.font, tileset.tile_width, tileset.tile_height)
+                    .with_font(text_font, width, height)
+                    .with_simple_console(80, 50, &tileset.font)
+                    .with_sparse_console(80, 50, text_font)
+            }
+        };
+        let mut context = builder
+            .with_title(&self.window_title)
+            .with_fullscreen(self.fullscreen)
+            .build()?;
+        if self.scanlines {
+            context.with_post_scanlines(true);
+        }
+        if tileset.is_some() {
+            context.set_active_console(TEXT_LAYER);
+        }
+        Ok(context)
+    }
+}
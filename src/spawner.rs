@@ -1,10 +1,16 @@
 use super::{
-    map::{Map, MAPCOUNT, MAPHEIGHT, MAPWIDTH},
+    encumbrance_system,
+    map::{Map, TileType, MAPCOUNT, MAPHEIGHT, MAPWIDTH},
+    map_builder,
     rect::Rect,
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DefenseBonus, EntryTrigger,
-    EquipmentSlot, Equippable, Hidden, HungerClock, HungerState, InflictsDamage, Item, MagicMapper,
-    MeleePowerBonus, Monster, Name, Player, Position, ProvidesFood, ProvidesHealing, RandomTable,
-    Ranged, Renderable, SerializeMe, SingleActivation, Viewshed,
+    spell_system, AbilityEffect, AreaOfEffect, BlocksTile, BurdenState, Bystander, CarriesItems,
+    CombatStats, Confusion, Consumable, Container, DefenseBonus, Encumbrance, EntryTrigger,
+    EquipmentSlot, Equippable, Equipped, Gold, Hidden, HungerClock, HungerState, InBackpack,
+    InContainer, InflictsDamage, Item, ItemValue, ItemWeight, KnownSpells, LeavesCorpse, Locked,
+    LootTable, MagicMapper, Mana, MeleePowerBonus, Monster, MonsterAbilities, MonsterAbility, Name,
+    Player, Portal, Position, ProvidesFood, ProvidesHealing, Purse, QuestItem, RandomTable, Ranged,
+    Renderable, SerializeMe, SingleActivation, TeachesSpell, TownPortal, Trapped, Vendor,
+    VendorKind, Viewshed, Weight,
 };
 use rltk::{RandomNumberGenerator, RGB};
 use specs::{
@@ -12,12 +18,11 @@ use specs::{
     saveload::{MarkedBuilder, SimpleMarker},
 };
 
-use std::collections::HashMap;
+use std::collections::HashSet;
 
-const MAX_MONSTERS: i32 = 4;
 const MAX_ITEMS: i32 = 2;
 
-fn bear_trap(ecs: &mut World, x: i32, y: i32) {
+fn bear_trap(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -34,10 +39,10 @@ fn bear_trap(ecs: &mut World, x: i32, y: i32) {
         .with(InflictsDamage { damage: 6 })
         .with(SingleActivation {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
+fn rations(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -53,10 +58,79 @@ fn rations(ecs: &mut World, x: i32, y: i32) {
         .with(ProvidesFood {})
         .with(Consumable {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+/// A chest, which may be locked, trapped or both. Its loot is added by `spawn_entity`.
+fn chest(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let (locked, trap) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let locked = rng.roll_dice(1, 3) == 1;
+        let trap = if rng.roll_dice(1, 4) == 1 {
+            Some(rng.roll_dice(2, 4))
+        } else {
+            None
+        };
+        (locked, trap)
+    };
+    let mut builder = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('■'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Chest".to_string(),
+        })
+        .with(Container {});
+    if locked {
+        builder = builder.with(Locked {});
+    }
+    if let Some(damage) = trap {
+        builder = builder.with(Trapped { damage });
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+fn bag(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('&'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Bag".to_string(),
+        })
+        .with(Item {})
+        .with(Container {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Rolls a container's loot table and puts what comes up inside it.
+fn fill_container(ecs: &mut World, container: Entity, idx: usize, table: &str) {
+    let loot = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let (table, rolls) = loot_table(table);
+        table.roll_distinct(rolls, &mut rng)
+    };
+    for name in loot.iter() {
+        if let Some(item) = spawn_entity(ecs, idx, name) {
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InContainer>()
+                .insert(item, InContainer { container })
+                .expect("Unable to insert container entry");
+        }
+    }
 }
 
-fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
+fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -72,7 +146,7 @@ fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
         .with(MagicMapper {})
         .with(Consumable {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
 /// Spawns the player and returns his/her entity object.
@@ -107,11 +181,28 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             state: HungerState::WellFed,
             duration: 20,
         })
+        .with(Encumbrance {
+            carried: 0,
+            capacity: encumbrance_system::capacity(5),
+            state: BurdenState::Unburdened,
+        })
+        .with(Purse { gold: 25 })
+        .with(Mana {
+            current: 10,
+            max: 10,
+            regen_timer: 4,
+        })
+        .with(KnownSpells {
+            spells: vec!["Magic Missile".to_string()],
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
+fn orc(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let gold = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(2, 6);
     monster(
         ecs,
         x,
@@ -124,10 +215,14 @@ fn orc(ecs: &mut World, x: i32, y: i32) {
             defense: 1,
             power: 4,
         },
-    );
+        gold,
+    )
 }
 
-fn goblin(ecs: &mut World, x: i32, y: i32) {
+fn goblin(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let gold = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, 6);
     monster(
         ecs,
         x,
@@ -140,7 +235,231 @@ fn goblin(ecs: &mut World, x: i32, y: i32) {
             defense: 1,
             power: 2,
         },
+        gold,
+    )
+}
+
+fn wolf(ecs: &mut World, x: i32, y: i32) -> Entity {
+    monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('w'),
+        "Wolf",
+        CombatStats {
+            max_hp: 8,
+            hp: 8,
+            defense: 0,
+            power: 3,
+        },
+        0,
+    )
+}
+
+fn goblin_chieftain(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let gold = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(3, 6);
+    let chieftain = monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('G'),
+        "Goblin Chieftain",
+        CombatStats {
+            max_hp: 14,
+            hp: 14,
+            defense: 2,
+            power: 4,
+        },
+        gold,
+    );
+    give_abilities(
+        ecs,
+        chieftain,
+        vec![ability(
+            AbilityEffect::Summon {
+                minion: "Goblin".to_string(),
+                count: 2,
+            },
+            20,
+            (0.0, 8.0),
+            50,
+        )],
+    )
+}
+
+fn goblin_shaman(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let gold = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(2, 6);
+    let shaman = monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('s'),
+        "Goblin Shaman",
+        CombatStats {
+            max_hp: 8,
+            hp: 8,
+            defense: 0,
+            power: 2,
+        },
+        gold,
+    );
+    give_abilities(
+        ecs,
+        shaman,
+        vec![
+            ability(AbilityEffect::Teleport, 12, (0.0, 1.5), 40),
+            ability(
+                AbilityEffect::SupportSpell {
+                    spell: "Heal".to_string(),
+                },
+                5,
+                (0.0, 6.0),
+                50,
+            ),
+            ability(
+                AbilityEffect::CastSpell {
+                    spell: "Magic Missile".to_string(),
+                },
+                4,
+                (2.0, 6.0),
+                100,
+            ),
+        ],
+    )
+}
+
+fn fire_drake(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let drake = monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('D'),
+        "Fire Drake",
+        CombatStats {
+            max_hp: 30,
+            hp: 30,
+            defense: 3,
+            power: 7,
+        },
+        0,
+    );
+    give_abilities(
+        ecs,
+        drake,
+        vec![ability(
+            AbilityEffect::CastSpell {
+                spell: "Fire Breath".to_string(),
+            },
+            6,
+            (1.5, 5.0),
+            100,
+        )],
+    )
+}
+
+fn skeleton(ecs: &mut World, x: i32, y: i32) -> Entity {
+    monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('z'),
+        "Skeleton",
+        CombatStats {
+            max_hp: 14,
+            hp: 14,
+            defense: 2,
+            power: 5,
+        },
+        0,
+    )
+}
+
+/// The final boss, guarding the amulet at the bottom of the dungeon.
+fn lich(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let lich = monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('L'),
+        "The Lich",
+        CombatStats {
+            max_hp: 120,
+            hp: 120,
+            defense: 4,
+            power: 9,
+        },
+        250,
     );
+    give_abilities(
+        ecs,
+        lich,
+        vec![
+            ability(AbilityEffect::Teleport, 25, (0.0, 1.5), 25),
+            ability(
+                AbilityEffect::SupportSpell {
+                    spell: "Heal".to_string(),
+                },
+                8,
+                (0.0, 6.0),
+                50,
+            ),
+            ability(
+                AbilityEffect::Summon {
+                    minion: "Skeleton".to_string(),
+                    count: 3,
+                },
+                15,
+                (0.0, 8.0),
+                75,
+            ),
+            ability(
+                AbilityEffect::CastSpell {
+                    spell: "Fireball".to_string(),
+                },
+                6,
+                (3.5, 6.0),
+                100,
+            ),
+            ability(
+                AbilityEffect::CastSpell {
+                    spell: "Magic Missile".to_string(),
+                },
+                2,
+                (1.5, 6.0),
+                100,
+            ),
+        ],
+    )
+}
+
+/// An ability that is ready to use, usable at `range` (min, max) from its target and at or
+/// below `hp_threshold` percent hit points.
+fn ability(
+    effect: AbilityEffect,
+    cooldown: i32,
+    range: (f32, f32),
+    hp_threshold: i32,
+) -> MonsterAbility {
+    MonsterAbility {
+        effect,
+        cooldown,
+        cooldown_remaining: 0,
+        min_range: range.0,
+        max_range: range.1,
+        hp_threshold,
+    }
+}
+
+/// Abilities are tried in order, so put the most situational ones first.
+fn give_abilities(ecs: &mut World, monster: Entity, abilities: Vec<MonsterAbility>) -> Entity {
+    ecs.write_storage::<MonsterAbilities>()
+        .insert(monster, MonsterAbilities { abilities })
+        .expect("Unable to insert abilities");
+    monster
 }
 
 fn monster<S: ToString>(
@@ -150,7 +469,8 @@ fn monster<S: ToString>(
     glyph: rltk::FontCharType,
     name: S,
     cs: CombatStats,
-) {
+    gold: i32,
+) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -170,79 +490,888 @@ fn monster<S: ToString>(
         })
         .with(BlocksTile {})
         .with(cs)
+        .with(Purse { gold })
+        .with(LootTable {
+            table: name.to_string(),
+        })
+        .with(LeavesCorpse {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn room_table(map_depth: i32) -> RandomTable {
+/// Any scroll, weighted by how useful it is at the given depth.
+fn scroll_table(map_depth: i32) -> RandomTable {
     RandomTable::new()
-        .add("Fireball Scroll", 2 + map_depth)
-        .add("Confusion Scroll", 2 + map_depth)
+        .at_depth(map_depth)
+        .add_weighted(
+            "Fireball Scroll",
+            Weight::Linear {
+                base: 2,
+                per_depth: 1,
+            },
+        )
+        .add_weighted(
+            "Confusion Scroll",
+            Weight::Linear {
+                base: 2,
+                per_depth: 1,
+            },
+        )
         .add("Magic Missile Scroll", 4)
+        .add("Magic Mapping Scroll", 2)
+        .add("Town Portal Scroll", 2)
+}
+
+fn spellbook_table(map_depth: i32) -> RandomTable {
+    RandomTable::new()
+        .at_depth(map_depth)
+        .add("Spellbook of Confusion", 3)
+        .add("Spellbook of Magic Mapping", 2)
+        .add("Spellbook of Fireball", 1)
+        .depths(3, i32::MAX)
+}
+
+/// Everything that can lie on the floor of a room.
+fn item_table(map_depth: i32) -> RandomTable {
+    RandomTable::new()
+        .at_depth(map_depth)
+        .add_table(
+            scroll_table(map_depth),
+            Weight::Linear {
+                base: 10,
+                per_depth: 2,
+            },
+        )
         .add("Health Potion", 7)
         .add("Dagger", 3)
+        .depths(1, 6)
         .add("Shield", 3)
-        .add("Longsword", map_depth - 1)
-        .add("Tower Shield", map_depth - 1)
+        .depths(1, 6)
+        .add_weighted(
+            "Longsword",
+            Weight::Linear {
+                base: -1,
+                per_depth: 1,
+            },
+        )
+        .add_weighted(
+            "Tower Shield",
+            Weight::Linear {
+                base: -1,
+                per_depth: 1,
+            },
+        )
         .add("Rations", 10)
-        .add("Magic Mapping Scroll", 2)
-        .add("Bear Trap", 2)
-        .add("Goblin", 10)
-        .add("Orc", 1 + map_depth)
+        .add("Bag", 2)
+        .add_table(spellbook_table(map_depth), Weight::Flat(1))
 }
 
-#[allow(clippy::map_entry)]
-pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32) {
-    let spawn_table = room_table(map_depth);
-    let mut spawn_points: HashMap<usize, String> = HashMap::new();
+/// Monster encounters. Groups are expanded into their members by `encounter_members`.
+fn encounter_table(map_depth: i32) -> RandomTable {
+    RandomTable::new()
+        .at_depth(map_depth)
+        .add_weighted(
+            "Goblin",
+            Weight::Peak {
+                depth: 1,
+                weight: 10,
+                falloff: 1,
+            },
+        )
+        .add_weighted(
+            "Orc",
+            Weight::Linear {
+                base: 1,
+                per_depth: 1,
+            },
+        )
+        .add("Wolf Pack", 4)
+        .depths(2, i32::MAX)
+        .add_weighted(
+            "Goblin War Band",
+            Weight::Linear {
+                base: -1,
+                per_depth: 2,
+            },
+        )
+        .depths(2, i32::MAX)
+        .add_weighted(
+            "Goblin Shaman",
+            Weight::Linear {
+                base: 0,
+                per_depth: 1,
+            },
+        )
+        .depths(3, i32::MAX)
+        .add_weighted(
+            "Fire Drake",
+            Weight::Linear {
+                base: -8,
+                per_depth: 2,
+            },
+        )
+        .depths(6, i32::MAX)
+}
+
+/// The monsters making up an encounter, leader first.
+fn encounter_members(encounter: &str, rng: &mut RandomNumberGenerator) -> Vec<String> {
+    match encounter {
+        "Wolf Pack" => vec!["Wolf".to_string(); 1 + rng.roll_dice(1, 3) as usize],
+        "Goblin War Band" => {
+            let mut members = vec!["Goblin Chieftain".to_string()];
+            members.extend(vec!["Goblin".to_string(); 1 + rng.roll_dice(1, 3) as usize]);
+            members
+        }
+        _ => vec![encounter.to_string()],
+    }
+}
+
+/// How much a monster counts against a level's difficulty budget.
+fn monster_cost(name: &str) -> i32 {
+    match name {
+        "Goblin" => 1,
+        "Wolf" => 2,
+        "Orc" => 3,
+        "Goblin Chieftain" => 4,
+        "Goblin Shaman" => 3,
+        "Fire Drake" => 8,
+        "Skeleton" => 3,
+        "The Lich" => 50,
+        _ => 0,
+    }
+}
+
+/// Experience for killing a monster: the tougher it is to place, the more it is worth.
+pub fn xp_value(name: &str) -> i32 {
+    monster_cost(name) * 10
+}
+
+/// The total monster cost allowed on a level.
+fn encounter_budget(map_depth: i32) -> i32 {
+    4 + map_depth * 3
+}
+
+fn is_free(map: &Map, idx: usize, taken: &HashSet<usize>) -> bool {
+    // Stairs aren't floor, so nothing ever lands on them
+    map.tiles[idx] == TileType::Floor && !taken.contains(&idx)
+}
+
+fn room_tiles(map: &Map, room: &Rect, taken: &HashSet<usize>) -> Vec<usize> {
+    let mut tiles = Vec::new();
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.xy_idx(x, y);
+            if is_free(map, idx, taken) {
+                tiles.push(idx);
+            }
+        }
+    }
+    tiles
+}
+
+/// Floor outside every room: corridors, and whatever a prefab section left open.
+fn corridor_tiles(map: &Map, taken: &HashSet<usize>) -> Vec<usize> {
+    (0..map.tiles.len())
+        .filter(|idx| is_free(map, *idx, taken))
+        .filter(|idx| {
+            let x = (*idx % MAPWIDTH) as i32;
+            let y = (*idx / MAPWIDTH) as i32;
+            !map.rooms
+                .iter()
+                .any(|r| x > r.x1 && x <= r.x2 && y > r.y1 && y <= r.y2)
+        })
+        .collect()
+}
+
+fn pick(tiles: &[usize], rng: &mut RandomNumberGenerator) -> Option<usize> {
+    if tiles.is_empty() {
+        None
+    } else {
+        Some(tiles[(rng.roll_dice(1, tiles.len() as i32) - 1) as usize])
+    }
+}
+
+/// A spot for an item: corners are likeliest, then tiles along a wall, then open floor.
+fn item_tile(
+    map: &Map,
+    room: &Rect,
+    taken: &HashSet<usize>,
+    rng: &mut RandomNumberGenerator,
+) -> Option<usize> {
+    let weighted: Vec<(usize, i32)> = room_tiles(map, room, taken)
+        .into_iter()
+        .map(|idx| {
+            let walls = [idx - 1, idx + 1, idx - MAPWIDTH, idx + MAPWIDTH]
+                .iter()
+                .filter(|n| map.tiles[**n] == TileType::Wall)
+                .count();
+            let weight = match walls {
+                0 => 1,
+                1 => 3,
+                _ => 6,
+            };
+            (idx, weight)
+        })
+        .collect();
+
+    let total: i32 = weighted.iter().map(|(_, w)| w).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.roll_dice(1, total) - 1;
+    for (idx, weight) in weighted {
+        if roll < weight {
+            return Some(idx);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+/// Tiles for a group: a random spot for the leader, the rest packed as closely around it as
+/// the room allows. Returns fewer tiles than asked for if the group doesn't fit.
+fn group_tiles(
+    map: &Map,
+    room: &Rect,
+    taken: &HashSet<usize>,
+    size: usize,
+    rng: &mut RandomNumberGenerator,
+) -> Vec<usize> {
+    let mut tiles = room_tiles(map, room, taken);
+    let leader = match pick(&tiles, rng) {
+        Some(idx) => idx,
+        None => return Vec::new(),
+    };
+    let (lx, ly) = ((leader % MAPWIDTH) as i32, (leader / MAPWIDTH) as i32);
+    tiles.sort_by_key(|idx| {
+        let (x, y) = ((idx % MAPWIDTH) as i32, (idx / MAPWIDTH) as i32);
+        i32::max(i32::abs(x - lx), i32::abs(y - ly))
+    });
+    // The leader's own tile sorts first, at distance zero
+    tiles.truncate(size);
+    tiles
+}
+
+/// Populates a generated level. Monsters arrive as encounters until the depth's difficulty
+/// budget is spent, each room gets a few items, every other level or so has a chest, and traps
+/// mostly go in corridors. `rooms`
+/// should leave out the player's starting room; `reserved` tiles are already spoken for.
+pub fn populate_level(ecs: &mut World, map: &Map, rooms: &[Rect], reserved: &[usize]) {
+    if rooms.is_empty() {
+        return;
+    }
+    let mut taken: HashSet<usize> = reserved.iter().copied().collect();
+    let mut spawn_points: Vec<(usize, String)> = Vec::new();
 
     // Scope to keep the borrow checker happy
     {
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_spawns = rng.roll_dice(1, MAX_MONSTERS + 3) + (map_depth - 1) - 3;
-
-        for _i in 0..num_spawns {
-            let mut added = false;
-            let mut tries = 0;
-            while !added && tries < 20 {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAPWIDTH) + x;
-                if !spawn_points.contains_key(&idx) {
-                    spawn_points.insert(idx, spawn_table.roll(&mut rng));
-                    added = true;
-                } else {
-                    tries += 1;
+        let random_room = |rng: &mut RandomNumberGenerator| {
+            &rooms[(rng.roll_dice(1, rooms.len() as i32) - 1) as usize]
+        };
+
+        let encounters = encounter_table(map.depth);
+        let mut budget = encounter_budget(map.depth);
+        let mut failures = 0;
+        while budget > 0 && failures < 20 {
+            let members = encounter_members(&encounters.roll(&mut rng), &mut rng);
+            let cost: i32 = members.iter().map(|m| monster_cost(m)).sum();
+            if cost == 0 || cost > budget {
+                failures += 1;
+                continue;
+            }
+            let room = random_room(&mut rng);
+            let tiles = group_tiles(map, room, &taken, members.len(), &mut rng);
+            if tiles.len() < members.len() {
+                failures += 1;
+                continue;
+            }
+            for (idx, name) in tiles.into_iter().zip(members) {
+                taken.insert(idx);
+                spawn_points.push((idx, name));
+            }
+            budget -= cost;
+        }
+
+        let items = item_table(map.depth);
+        for room in rooms.iter() {
+            let num_items = rng.roll_dice(1, MAX_ITEMS + 1) - 1;
+            for _i in 0..num_items {
+                if let Some(idx) = item_tile(map, room, &taken, &mut rng) {
+                    taken.insert(idx);
+                    spawn_points.push((idx, items.roll(&mut rng)));
                 }
             }
         }
+
+        if rng.roll_dice(1, 2) == 1 {
+            let room = random_room(&mut rng);
+            if let Some(idx) = item_tile(map, room, &taken, &mut rng) {
+                taken.insert(idx);
+                spawn_points.push((idx, "Chest".to_string()));
+            }
+        }
+
+        let num_traps = rng.roll_dice(1, 2) + map.depth / 3;
+        for _i in 0..num_traps {
+            let corridors = corridor_tiles(map, &taken);
+            let idx = if !corridors.is_empty() && rng.roll_dice(1, 4) > 1 {
+                pick(&corridors, &mut rng)
+            } else {
+                let room = random_room(&mut rng);
+                pick(&room_tiles(map, room, &taken), &mut rng)
+            };
+            if let Some(idx) = idx {
+                taken.insert(idx);
+                spawn_points.push((idx, "Bear Trap".to_string()));
+            }
+        }
+    }
+
+    for (idx, name) in spawn_points.iter() {
+        spawn_entity(ecs, *idx, name);
     }
+}
+
+/// Spawns a named entity at a map index. Unknown names are ignored.
+pub fn spawn_entity(ecs: &mut World, idx: usize, name: &str) -> Option<Entity> {
+    let x = (idx % MAPWIDTH) as i32;
+    let y = (idx / MAPWIDTH) as i32;
+
+    let entity = match name {
+        "Goblin" => goblin(ecs, x, y),
+        "Orc" => orc(ecs, x, y),
+        "Wolf" => wolf(ecs, x, y),
+        "Goblin Chieftain" => goblin_chieftain(ecs, x, y),
+        "Goblin Shaman" => goblin_shaman(ecs, x, y),
+        "Fire Drake" => fire_drake(ecs, x, y),
+        "Skeleton" => skeleton(ecs, x, y),
+        "The Lich" => lich(ecs, x, y),
+        "Amulet of the Ancients" => amulet(ecs, x, y),
+        "Portal Home" => portal(ecs, x, y, map_builder::TOWN_DEPTH),
+        "Health Potion" => health_potion(ecs, x, y),
+        "Fireball Scroll" => fireball_scroll(ecs, x, y),
+        "Confusion Scroll" => confusion_scroll(ecs, x, y),
+        "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
+        "Dagger" => dagger(ecs, x, y),
+        "Shield" => shield(ecs, x, y),
+        "Longsword" => longsword(ecs, x, y),
+        "Tower Shield" => tower_shield(ecs, x, y),
+        "Rations" => rations(ecs, x, y),
+        "Magic Mapping Scroll" => magic_mapping_scroll(ecs, x, y),
+        "Town Portal Scroll" => town_portal_scroll(ecs, x, y),
+        "Spellbook of Confusion" => spellbook(ecs, x, y, "Confusion"),
+        "Spellbook of Fireball" => spellbook(ecs, x, y, "Fireball"),
+        "Spellbook of Magic Mapping" => spellbook(ecs, x, y, "Magic Mapping"),
+        "Bear Trap" => bear_trap(ecs, x, y),
+        "Chest" => chest(ecs, x, y),
+        "Bag" => bag(ecs, x, y),
+        "Blacksmith" => vendor(ecs, x, y, "Blacksmith", VendorKind::Blacksmith),
+        "Alchemist" => vendor(ecs, x, y, "Alchemist", VendorKind::Alchemist),
+        "Barkeep" => vendor(ecs, x, y, "Barkeep", VendorKind::Tavern),
+        "Townsperson" => bystander(ecs, x, y, rltk::to_cp437('p'), "Townsperson"),
+        "Drunk" => bystander(ecs, x, y, rltk::to_cp437('d'), "Drunk"),
+        _ => return None,
+    };
 
-    // Actually spawn the monsters
-    for spawn in spawn_points.iter() {
-        let x = (*spawn.0 % MAPWIDTH) as i32;
-        let y = (*spawn.0 / MAPWIDTH) as i32;
-
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(ecs, x, y),
-            "Orc" => orc(ecs, x, y),
-            "Health Potion" => health_potion(ecs, x, y),
-            "Fireball Scroll" => fireball_scroll(ecs, x, y),
-            "Confusion Scroll" => confusion_scroll(ecs, x, y),
-            "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
-            "Dagger" => dagger(ecs, x, y),
-            "Shield" => shield(ecs, x, y),
-            "Longsword" => longsword(ecs, x, y),
-            "Tower Shield" => tower_shield(ecs, x, y),
-            "Rations" => rations(ecs, x, y),
-            "Magic Mapping Scroll" => magic_mapping_scroll(ecs, x, y),
-            "Bear Trap" => bear_trap(ecs, x, y),
-            _ => {}
+    if let Some(value) = item_value(name) {
+        ecs.write_storage::<ItemValue>()
+            .insert(entity, ItemValue { gold: value })
+            .expect("Unable to insert item value");
+    }
+    if name == "Chest" {
+        fill_container(ecs, entity, idx, "Chest");
+    }
+    let (kit, rolls) = monster_kit(name);
+    if rolls > 0 {
+        ecs.write_storage::<CarriesItems>()
+            .insert(entity, CarriesItems {})
+            .expect("Unable to insert item carrier");
+        let items = kit.roll_distinct(rolls, &mut ecs.write_resource::<RandomNumberGenerator>());
+        for item in items.iter() {
+            give_item(ecs, entity, idx, item);
         }
     }
+    if let Some(pounds) = item_weight(name) {
+        ecs.write_storage::<ItemWeight>()
+            .insert(entity, ItemWeight { pounds })
+            .expect("Unable to insert item weight");
+    }
+
+    Some(entity)
+}
+
+/// What a monster may drop when it dies, keyed by its `LootTable`, and how many different
+/// items to roll from it. "Nothing" spawns nothing.
+pub fn loot_table(table: &str) -> (RandomTable, usize) {
+    match table {
+        "Goblin" => (
+            RandomTable::new()
+                .add("Nothing", 8)
+                .add("Rations", 3)
+                .add("Health Potion", 2)
+                .add("Dagger", 1),
+            1,
+        ),
+        "Orc" => (
+            RandomTable::new()
+                .add("Nothing", 24)
+                .add("Health Potion", 3)
+                .add("Magic Missile Scroll", 1)
+                .add("Town Portal Scroll", 1)
+                .add("Shield", 1)
+                .add("Longsword", 1),
+            2,
+        ),
+        "Goblin Shaman" => (
+            RandomTable::new()
+                .add("Nothing", 4)
+                .add("Magic Missile Scroll", 2)
+                .add("Health Potion", 2)
+                .add("Spellbook of Confusion", 1),
+            1,
+        ),
+        "Fire Drake" => (
+            RandomTable::new()
+                .add("Nothing", 2)
+                .add("Fireball Scroll", 2)
+                .add("Spellbook of Fireball", 1)
+                .add("Tower Shield", 1)
+                .add("Longsword", 1),
+            2,
+        ),
+        "The Lich" => (
+            RandomTable::new()
+                .add("Spellbook of Fireball", 2)
+                .add("Health Potion", 2)
+                .add("Tower Shield", 1)
+                .add("Longsword", 1),
+            2,
+        ),
+        "Goblin Chieftain" => (
+            RandomTable::new()
+                .add("Nothing", 4)
+                .add("Health Potion", 3)
+                .add("Confusion Scroll", 1)
+                .add("Shield", 1)
+                .add("Longsword", 1),
+            1,
+        ),
+        "Chest" => (
+            RandomTable::new()
+                .add("Nothing", 2)
+                .add("Health Potion", 4)
+                .add("Magic Missile Scroll", 2)
+                .add("Fireball Scroll", 1)
+                .add("Confusion Scroll", 1)
+                .add("Town Portal Scroll", 1)
+                .add("Rations", 2)
+                .add("Bag", 1)
+                .add("Longsword", 1)
+                .add("Shield", 1),
+            3,
+        ),
+        _ => (RandomTable::new(), 0),
+    }
+}
+
+/// What a monster may carry when it spawns, in the same form as `loot_table`. Monsters with
+/// a kit also pick up and use items they find.
+pub fn monster_kit(name: &str) -> (RandomTable, usize) {
+    match name {
+        "Goblin" => (
+            RandomTable::new()
+                .add("Nothing", 3)
+                .add("Dagger", 2)
+                .add("Health Potion", 1),
+            1,
+        ),
+        "Orc" => (
+            RandomTable::new()
+                .add("Nothing", 3)
+                .add("Dagger", 2)
+                .add("Shield", 2)
+                .add("Health Potion", 2)
+                .add("Longsword", 1),
+            2,
+        ),
+        "Goblin Chieftain" => (
+            RandomTable::new()
+                .add("Longsword", 2)
+                .add("Shield", 2)
+                .add("Health Potion", 2),
+            2,
+        ),
+        "Goblin Shaman" => (
+            RandomTable::new()
+                .add("Nothing", 1)
+                .add("Magic Missile Scroll", 2)
+                .add("Health Potion", 2),
+            1,
+        ),
+        "Skeleton" => (
+            RandomTable::new()
+                .add("Nothing", 3)
+                .add("Dagger", 1)
+                .add("Shield", 1),
+            1,
+        ),
+        "The Lich" => (
+            RandomTable::new()
+                .add("Fireball Scroll", 1)
+                .add("Health Potion", 1),
+            2,
+        ),
+        _ => (RandomTable::new(), 0),
+    }
+}
+
+/// Spawns an item straight into a monster's hands: worn if it can be and the slot is free,
+/// otherwise carried.
+fn give_item(ecs: &mut World, owner: Entity, idx: usize, name: &str) {
+    let item = match spawn_entity(ecs, idx, name) {
+        Some(item) => item,
+        None => return,
+    };
+    ecs.write_storage::<Position>().remove(item);
+    let slot = ecs
+        .read_storage::<Equippable>()
+        .get(item)
+        .map(|equippable| equippable.slot)
+        .filter(|slot| {
+            !ecs.read_storage::<Equipped>()
+                .join()
+                .any(|worn| worn.owner == owner && worn.slot == *slot)
+        });
+    if let Some(slot) = slot {
+        ecs.write_storage::<Equipped>()
+            .insert(item, Equipped { owner, slot })
+            .expect("Unable to equip item");
+    } else {
+        ecs.write_storage::<InBackpack>()
+            .insert(item, InBackpack { owner })
+            .expect("Unable to insert backpack entry");
+    }
+}
+
+/// Spawns the remains of a dead monster. Corpses can be eaten, if you're hungry enough.
+pub fn corpse(ecs: &mut World, x: i32, y: i32, victim: &str) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::DARK_RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 3,
+        })
+        .with(Name {
+            name: format!("{} Corpse", victim),
+        })
+        .with(Item {})
+        .with(ProvidesFood {})
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
+/// The price a vendor asks for an item. Vendors buy items back for half this.
+pub fn item_value(name: &str) -> Option<i32> {
+    match name {
+        "Health Potion" => Some(20),
+        "Fireball Scroll" => Some(100),
+        "Confusion Scroll" => Some(50),
+        "Magic Missile Scroll" => Some(50),
+        "Dagger" => Some(20),
+        "Shield" => Some(30),
+        "Longsword" => Some(80),
+        "Tower Shield" => Some(80),
+        "Rations" => Some(5),
+        "Bag" => Some(15),
+        "Magic Mapping Scroll" => Some(50),
+        "Town Portal Scroll" => Some(40),
+        "Spellbook of Confusion" => Some(150),
+        "Spellbook of Magic Mapping" => Some(150),
+        "Spellbook of Fireball" => Some(300),
+        _ => None,
+    }
+}
+
+/// How heavy an item is, in pounds.
+pub fn item_weight(name: &str) -> Option<i32> {
+    match name {
+        "Amulet of the Ancients" => Some(1),
+        "Health Potion" => Some(1),
+        "Fireball Scroll" => Some(1),
+        "Confusion Scroll" => Some(1),
+        "Magic Missile Scroll" => Some(1),
+        "Dagger" => Some(2),
+        "Shield" => Some(8),
+        "Longsword" => Some(6),
+        "Tower Shield" => Some(20),
+        "Rations" => Some(2),
+        "Bag" => Some(1),
+        "Magic Mapping Scroll" => Some(1),
+        "Town Portal Scroll" => Some(1),
+        "Spellbook of Confusion" => Some(4),
+        "Spellbook of Magic Mapping" => Some(4),
+        "Spellbook of Fireball" => Some(4),
+        _ => None,
+    }
+}
+
+/// Flavour text shown when looking at something.
+pub fn description(name: &str) -> Option<&'static str> {
+    let text = match name {
+        "Player" => "That's you, a hopeful adventurer.",
+        "Orc" => "A brutish orc, strong but none too bright.",
+        "Goblin" => "A scrawny goblin. Weak alone, a nuisance in numbers.",
+        "Wolf" => "A hungry grey wolf. Where there is one, there is a pack.",
+        "Goblin Chieftain" => "A scarred goblin leader who never fights without backup.",
+        "Goblin Shaman" => "A goblin draped in bones, muttering spells and healing its kin.",
+        "Fire Drake" => "A young dragon. Keep your distance from its fiery breath.",
+        "Skeleton" => "Bones held together by the Lich's will.",
+        "The Lich" => "An undead sorcerer of terrible power, keeper of the Amulet.",
+        "Blacksmith" => "The town smith, selling arms and armour.",
+        "Alchemist" => "A purveyor of potions, scrolls and spellbooks.",
+        "Barkeep" => "The keeper of the tavern. Sells food for the road.",
+        "Townsperson" => "A local going about their business.",
+        "Drunk" => "A patron who has had a few too many.",
+        "Health Potion" => "A red potion that closes wounds.",
+        "Magic Missile Scroll" => "Fires a bolt of force at a single target.",
+        "Fireball Scroll" => "Engulfs an area in flames.",
+        "Confusion Scroll" => "Befuddles a creature for a few turns.",
+        "Scroll of Magic Mapping" => "Reveals the layout of the level.",
+        "Town Portal Scroll" => "Opens a way back to town, and leaves one open to return by.",
+        "Spellbook of Confusion" | "Spellbook of Fireball" | "Spellbook of Magic Mapping" => {
+            "Reading it teaches you the spell for good."
+        }
+        "Rations" => "Dried meat and hard bread. Keeps hunger at bay.",
+        "Dagger" => "A short, sharp blade.",
+        "Shield" => "A round wooden shield.",
+        "Longsword" => "A well balanced sword of good steel.",
+        "Tower Shield" => "A heavy shield that covers almost all of you.",
+        "Bear Trap" => "Steel jaws waiting for an unwary foot.",
+        "Chest" => "A sturdy wooden chest. Stand on it and pick up to open it.",
+        "Bag" => "A leather bag for keeping your pack in order.",
+        "Amulet of the Ancients" => "The prize of the dungeon. Bring it back to town.",
+        _ if name.ends_with(" Gold") => "Coins, as good here as anywhere.",
+        _ if name.ends_with(" Corpse") => "Edible, if you are desperate.",
+        _ if name.starts_with("Portal to") => "A shimmering gateway.",
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// What each kind of vendor has for sale.
+pub fn vendor_wares(kind: VendorKind) -> Vec<&'static str> {
+    match kind {
+        VendorKind::Blacksmith => vec!["Dagger", "Shield", "Longsword", "Tower Shield"],
+        VendorKind::Alchemist => vec![
+            "Health Potion",
+            "Magic Missile Scroll",
+            "Confusion Scroll",
+            "Fireball Scroll",
+            "Magic Mapping Scroll",
+            "Town Portal Scroll",
+            "Spellbook of Confusion",
+            "Spellbook of Magic Mapping",
+        ],
+        VendorKind::Tavern => vec!["Rations", "Bag"],
+    }
+}
+
+fn vendor<S: ToString>(ecs: &mut World, x: i32, y: i32, name: S, kind: VendorKind) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(BlocksTile {})
+        .with(Vendor { kind })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn bystander<S: ToString>(
+    ecs: &mut World,
+    x: i32,
+    y: i32,
+    glyph: rltk::FontCharType,
+    name: S,
+) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph,
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(BlocksTile {})
+        .with(Bystander {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Spawns a pile of gold, as dropped by a dead monster.
+pub fn gold(ecs: &mut World, x: i32, y: i32, amount: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('$'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("{} Gold", amount),
+        })
+        .with(Item {})
+        .with(Gold { amount })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Spawns the way back down from town, left behind by a Town Portal Scroll.
+pub fn portal(ecs: &mut World, x: i32, y: i32, depth: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('Θ'),
+            fg: RGB::named(rltk::MAGENTA),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: if depth == map_builder::TOWN_DEPTH {
+                "Portal to town".to_string()
+            } else {
+                format!("Portal to depth {}", depth)
+            },
+        })
+        .with(Portal { depth })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn amulet(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('"'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Amulet of the Ancients".to_string(),
+        })
+        .with(Item {})
+        .with(QuestItem {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn town_portal_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::MAGENTA),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Town Portal Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(TownPortal {})
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn spellbook(ecs: &mut World, x: i32, y: i32, spell: &str) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('+'),
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("Spellbook of {}", spell),
+        })
+        .with(Item {})
+        .with(TeachesSpell {
+            spell: spell.to_string(),
+        })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// How far a spell's blast reaches from where it lands, if it has one.
+pub fn spell_blast_radius(spell: &str) -> Option<i32> {
+    match spell {
+        "Fireball" => Some(3),
+        "Fire Breath" => Some(1),
+        _ => None,
+    }
+}
+
+/// The effect of a spell being cast, as a short-lived item carrying the same components as
+/// the matching scroll. It is never saved: `ItemUseSystem` consumes it the turn it's cast.
+pub fn spell_effect(ecs: &mut World, spell: &str) -> Option<Entity> {
+    spell_system::spell_index(spell)?;
+    let builder = ecs
+        .create_entity()
+        .with(Name {
+            name: spell.to_string(),
+        })
+        .with(Consumable {});
+    let effect = match spell {
+        "Magic Missile" => builder
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 8 }),
+        "Fireball" => builder
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 20 }),
+        "Confusion" => builder
+            .with(Ranged { range: 6 })
+            .with(Confusion { turns: 4 }),
+        "Magic Mapping" => builder.with(MagicMapper {}),
+        "Heal" => builder
+            .with(Ranged { range: 6 })
+            .with(ProvidesHealing { heal_amount: 8 }),
+        "Fire Breath" => builder
+            .with(Ranged { range: 5 })
+            .with(InflictsDamage { damage: 10 }),
+        _ => builder,
+    };
+    let effect = match spell_blast_radius(spell) {
+        Some(radius) => effect.with(AreaOfEffect { radius }),
+        None => effect,
+    };
+    Some(effect.build())
+}
+
+fn health_potion(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -258,10 +1387,10 @@ fn health_potion(ecs: &mut World, x: i32, y: i32) {
         .with(Consumable {})
         .with(ProvidesHealing { heal_amount: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -278,10 +1407,10 @@ fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
         .with(Ranged { range: 6 })
         .with(InflictsDamage { damage: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
+fn fireball_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -299,10 +1428,10 @@ fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
         .with(InflictsDamage { damage: 20 })
         .with(AreaOfEffect { radius: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
+fn confusion_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -319,10 +1448,10 @@ fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
         .with(Ranged { range: 6 })
         .with(Confusion { turns: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
+fn dagger(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -340,10 +1469,10 @@ fn dagger(ecs: &mut World, x: i32, y: i32) {
         })
         .with(MeleePowerBonus { power: 2 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
+fn shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -361,10 +1490,10 @@ fn shield(ecs: &mut World, x: i32, y: i32) {
         })
         .with(DefenseBonus { defense: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
+fn longsword(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -382,10 +1511,10 @@ fn longsword(ecs: &mut World, x: i32, y: i32) {
         })
         .with(MeleePowerBonus { power: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
+fn tower_shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -403,5 +1532,5 @@ fn tower_shield(ecs: &mut World, x: i32, y: i32) {
         })
         .with(DefenseBonus { defense: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
@@ -0,0 +1,413 @@
+use super::{
+    achievements::{Achievements, ACHIEVEMENTS},
+    gamelog::GameLog,
+    gui, highscores,
+    keybindings::{self, KeyBindings, ACTIONS},
+    map_builder::TOWN_DEPTH,
+    menu, morgue, spell_system, CombatStats, Encumbrance, Equipped, Hidden, KnownSpells, Map, Name,
+    Position, RunState,
+};
+use rltk::{Point, Rltk};
+use specs::prelude::*;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// Describes the game as plain lines of text, for a screen reader or speech synthesiser to
+/// read out: new log messages, things coming into view, and whatever menu is open.
+pub struct Speech {
+    out: Box<dyn Write + Send + Sync>,
+    /// What was last said about the current screen, so it is only said again when it changes.
+    screen: String,
+    hovered: Vec<String>,
+    seen: HashSet<Entity>,
+}
+
+/// Stdout, unless the game itself is being drawn there, in which case stderr.
+fn console() -> Box<dyn Write + Send + Sync> {
+    if cfg!(feature = "terminal") {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+impl Speech {
+    /// Speaks to `output` (a file or named pipe), or to the console if that is empty or can't
+    /// be opened.
+    pub fn new(output: &str) -> Speech {
+        let out = if output.is_empty() {
+            console()
+        } else {
+            match OpenOptions::new().create(true).append(true).open(output) {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    let mut out = console();
+                    let _ = writeln!(out, "Could not open {} for speech: {}", output, e);
+                    out
+                }
+            }
+        };
+        Speech {
+            out,
+            screen: String::new(),
+            hovered: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    fn say(&mut self, text: &str) {
+        if writeln!(self.out, "{}", text)
+            .and_then(|_| self.out.flush())
+            .is_err()
+        {
+            rltk::console::log("Unable to write speech output");
+        }
+    }
+}
+
+/// Turns speech on or off.
+pub fn enable(ecs: &mut World, on: bool, output: &str) {
+    if on {
+        ecs.insert(Speech::new(output));
+    } else {
+        ecs.remove::<Speech>();
+    }
+    ecs.write_resource::<GameLog>().announce = on;
+}
+
+/// Which way, and how far, `to` is from `from`.
+fn direction(from: Point, to: Point) -> String {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let mut parts = Vec::new();
+    if dy < 0 {
+        parts.push(format!("{} north", -dy));
+    } else if dy > 0 {
+        parts.push(format!("{} south", dy));
+    }
+    if dx > 0 {
+        parts.push(format!("{} east", dx));
+    } else if dx < 0 {
+        parts.push(format!("{} west", -dx));
+    }
+    if parts.is_empty() {
+        "here".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Everything named the player can see, with where it is.
+fn visible_things(ecs: &World) -> Vec<(Entity, String)> {
+    let map = ecs.fetch::<Map>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let names = ecs.read_storage::<Name>();
+    let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
+
+    (&entities, &names, &positions, !&hidden)
+        .join()
+        .filter(|(entity, _, pos, _)| {
+            *entity != player_entity && map.visible_tiles[map.xy_idx(pos.x, pos.y)]
+        })
+        .map(|(entity, name, pos, _)| {
+            let at = direction(player_pos, Point::new(pos.x, pos.y));
+            (entity, format!("{}, {}", name.name, at))
+        })
+        .collect()
+}
+
+/// The lines a describing function gives, as one sentence-per-line string.
+fn plain(lines: Vec<(rltk::RGB, String)>) -> String {
+    lines
+        .into_iter()
+        .map(|(_, line)| line)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join(". ")
+}
+
+/// Menu entries, lettered as the item menus letter them.
+fn lettered(items: Vec<String>) -> String {
+    if items.is_empty() {
+        return "Nothing.".to_string();
+    }
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, name)| format!("{}: {}", (b'a' + i as u8) as char, name))
+        .collect::<Vec<String>>()
+        .join(". ")
+}
+
+/// Items worn by the player.
+fn worn_choices(ecs: &World) -> String {
+    let player_entity = *ecs.fetch::<Entity>();
+    lettered(
+        (&ecs.read_storage::<Equipped>(), &ecs.read_storage::<Name>())
+            .join()
+            .filter(|(equipped, _)| equipped.owner == player_entity)
+            .map(|(_, name)| name.name.clone())
+            .collect(),
+    )
+}
+
+/// One page of stacked items, as the item menus show it.
+fn stack_choices(ecs: &World, stacks: Vec<gui::ItemStack>, page: usize) -> String {
+    let pages = usize::max(1, stacks.len().div_ceil(gui::STACKS_PER_PAGE));
+    let page = usize::min(page, pages - 1);
+    let items = stacks
+        .iter()
+        .skip(page * gui::STACKS_PER_PAGE)
+        .take(gui::STACKS_PER_PAGE)
+        .map(|stack| stack.label())
+        .collect();
+    let weight = ecs
+        .read_storage::<Encumbrance>()
+        .get(*ecs.fetch::<Entity>())
+        .map_or(String::new(), |load| {
+            format!("Carrying {} of {} pounds. ", load.carried, load.capacity)
+        });
+    format!(
+        "{}Page {} of {}. {}",
+        weight,
+        page + 1,
+        pages,
+        lettered(items)
+    )
+}
+
+/// What the current screen shows, in words.
+fn screen_text(ecs: &World, runstate: RunState) -> String {
+    let player_entity = *ecs.fetch::<Entity>();
+    match runstate {
+        RunState::MainMenu { menu_selection } => {
+            format!("Main menu: {}", menu_selection.label())
+        }
+        RunState::ShowInventory { page, sort } => format!(
+            "Use which item? Sorted by {}. {}",
+            sort.label(),
+            stack_choices(ecs, gui::carried_stacks(ecs, player_entity, sort), page)
+        ),
+        RunState::ShowDropItem { page, sort } => format!(
+            "Drop which item? Sorted by {}. {}",
+            sort.label(),
+            stack_choices(ecs, gui::carried_stacks(ecs, player_entity, sort), page)
+        ),
+        RunState::ShowPickup { page } => {
+            let pos = *ecs.fetch::<Point>();
+            format!(
+                "Pick up which item? {}",
+                stack_choices(ecs, gui::stacks_at(ecs, pos.x, pos.y), page)
+            )
+        }
+        RunState::ShowContainer {
+            container,
+            mode,
+            page,
+        } => {
+            let name = ecs
+                .read_storage::<Name>()
+                .get(container)
+                .map_or("container".to_string(), |n| n.name.clone());
+            let stacks = gui::container_stacks(ecs, container, mode);
+            match mode {
+                gui::ContainerMode::Take => format!(
+                    "Take what from the {}? {}",
+                    name,
+                    stack_choices(ecs, stacks, page)
+                ),
+                gui::ContainerMode::Put => format!(
+                    "Put what in the {}? {}",
+                    name,
+                    stack_choices(ecs, stacks, page)
+                ),
+            }
+        }
+        RunState::ShowDropCount { item, count } => {
+            let name = ecs
+                .read_storage::<Name>()
+                .get(item)
+                .map_or("item".to_string(), |n| n.name.clone());
+            format!("Drop how many {}? {}", name, count)
+        }
+        RunState::ShowRemoveItem => format!("Remove which item? {}", worn_choices(ecs)),
+        RunState::ShowSpells => {
+            let known = ecs.read_storage::<KnownSpells>();
+            let spells: Vec<String> = known
+                .get(player_entity)
+                .map(|known| known.spells.clone())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| spell_system::spell_index(name))
+                .enumerate()
+                .map(|(i, index)| {
+                    let spell = &spell_system::SPELLS[index];
+                    format!(
+                        "{}: {}, {} mana",
+                        (b'a' + i as u8) as char,
+                        spell.name,
+                        spell.mana_cost
+                    )
+                })
+                .collect();
+            format!("Cast which spell? {}", spells.join(". "))
+        }
+        RunState::ShowTargeting { item, target, .. } => {
+            let name = ecs
+                .read_storage::<Name>()
+                .get(item)
+                .map_or("item".to_string(), |n| n.name.clone());
+            let cursor = target.unwrap_or(*ecs.fetch::<Point>());
+            format!(
+                "Aiming {}. {}",
+                name,
+                plain(gui::describe_tile(ecs, cursor))
+            )
+        }
+        RunState::ShowSpellTargeting { spell, target, .. } => {
+            let cursor = target.unwrap_or(*ecs.fetch::<Point>());
+            format!(
+                "Aiming {}. {}",
+                spell_system::SPELLS[spell].name,
+                plain(gui::describe_tile(ecs, cursor))
+            )
+        }
+        RunState::LookMode { cursor } => {
+            format!("Looking. {}", plain(gui::describe_tile(ecs, cursor)))
+        }
+        RunState::ShowHighScores => {
+            let scores: Vec<String> = highscores::load_scores()
+                .iter()
+                .take(5)
+                .enumerate()
+                .map(|(i, s)| format!("{}: {}, {} points", i + 1, s.name, s.score))
+                .collect();
+            format!("High scores. {}", scores.join(". "))
+        }
+        RunState::ShowAchievements => {
+            let unlocked = ecs.fetch::<Achievements>();
+            let names: Vec<&str> = ACHIEVEMENTS
+                .iter()
+                .filter(|a| unlocked.unlocked.contains_key(a.name))
+                .map(|a| a.name)
+                .collect();
+            format!(
+                "Achievements, {} of {} unlocked. {}",
+                names.len(),
+                ACHIEVEMENTS.len(),
+                names.join(". ")
+            )
+        }
+        RunState::ShowOptions { selected } => {
+            let rows = menu::option_rows(ecs);
+            let (label, value) = &rows[selected];
+            format!("Options: {}, {}", label.trim_end_matches(" *"), value)
+        }
+        RunState::ShowHelp => {
+            let bindings = ecs.fetch::<KeyBindings>();
+            let lines: Vec<String> = ACTIONS
+                .iter()
+                .map(|action| {
+                    let keys: Vec<String> = bindings
+                        .keys_for(*action)
+                        .into_iter()
+                        .map(keybindings::key_label)
+                        .collect();
+                    format!("{}: {}", action.label(), keys.join(", "))
+                })
+                .collect();
+            format!("Controls. {}", lines.join(". "))
+        }
+        RunState::ShowLog { filter, .. } => format!(
+            "Message log, {}",
+            filter.map_or("All", |category| category.label())
+        ),
+        RunState::ShowVendor { mode, .. } => match mode {
+            gui::VendorMode::Buy => "Trading: buying".to_string(),
+            gui::VendorMode::Sell => "Trading: selling".to_string(),
+        },
+        RunState::GameOver { .. } | RunState::Victory { .. } => {
+            let title = if matches!(runstate, RunState::Victory { .. }) {
+                "You won!"
+            } else {
+                "You died."
+            };
+            let summary: Vec<String> = ecs
+                .fetch::<morgue::Morgue>()
+                .lines
+                .iter()
+                .skip_while(|line| line.is_empty())
+                .take_while(|line| !line.is_empty())
+                .cloned()
+                .collect();
+            format!("{} {}", title, summary.join(". "))
+        }
+        _ => {
+            // On the map: the same status the bottom panel shows
+            let map = ecs.fetch::<Map>();
+            let depth = if map.depth == TOWN_DEPTH {
+                "In town".to_string()
+            } else {
+                format!("Depth {}", map.depth)
+            };
+            let burden = ecs
+                .read_storage::<Encumbrance>()
+                .get(player_entity)
+                .and_then(|load| gui::burden_label(load.state))
+                .map_or(String::new(), |label| format!(". {}", label));
+            match ecs.read_storage::<CombatStats>().get(player_entity) {
+                Some(stats) => format!("{}. HP {} of {}{}", depth, stats.hp, stats.max_hp, burden),
+                None => depth + &burden,
+            }
+        }
+    }
+}
+
+/// Says anything new since last frame. Does nothing unless speech is on.
+pub fn speak(ecs: &mut World, ctx: &Rltk) {
+    if !ecs.has_value::<Speech>() {
+        return;
+    }
+    let runstate = *ecs.fetch::<RunState>();
+    let on_map = matches!(runstate, RunState::AwaitingInput);
+    let messages = ecs.write_resource::<GameLog>().take_new();
+    let screen = screen_text(ecs, runstate);
+    let visible = if on_map {
+        visible_things(ecs)
+    } else {
+        Vec::new()
+    };
+    let mouse = ctx.mouse_pos();
+    let hovered = if on_map {
+        gui::tooltip_names(ecs, mouse.0, mouse.1)
+    } else {
+        Vec::new()
+    };
+
+    let mut speech = ecs.write_resource::<Speech>();
+    for message in messages.iter() {
+        speech.say(message);
+    }
+    if screen != speech.screen {
+        speech.say(&screen);
+        speech.screen = screen;
+    }
+    if on_map {
+        let new_sights: Vec<String> = visible
+            .iter()
+            .filter(|(entity, _)| !speech.seen.contains(entity))
+            .map(|(_, text)| text.clone())
+            .collect();
+        if !new_sights.is_empty() {
+            speech.say(&format!("You see: {}", new_sights.join("; ")));
+        }
+        speech.seen = visible.into_iter().map(|(entity, _)| entity).collect();
+    }
+    if !hovered.is_empty() && hovered != speech.hovered {
+        speech.say(&format!("Mouse over: {}", hovered.join(", ")));
+    }
+    speech.hovered = hovered;
+}
@@ -0,0 +1,216 @@
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    map::Map,
+    morgue::{self, RunStatistics},
+    spawner, KnownSpells, Mana, Name, Position, RunState, WantsToCastSpell, WantsToSummon,
+    WantsToUseItem,
+};
+use specs::prelude::*;
+
+/// Player turns (or monster turns, for monsters) it takes to recover one point of mana.
+const MANA_REGEN_TURNS: i32 = 4;
+
+pub struct Spell {
+    pub name: &'static str,
+    pub mana_cost: i32,
+    /// Targeted spells have a range; the rest affect the caster.
+    pub range: Option<i32>,
+}
+
+/// Every spell in the game. The effects themselves live in `spawner::spell_effect`.
+pub const SPELLS: &[Spell] = &[
+    Spell {
+        name: "Magic Missile",
+        mana_cost: 2,
+        range: Some(6),
+    },
+    Spell {
+        name: "Confusion",
+        mana_cost: 4,
+        range: Some(6),
+    },
+    Spell {
+        name: "Fireball",
+        mana_cost: 8,
+        range: Some(6),
+    },
+    Spell {
+        name: "Magic Mapping",
+        mana_cost: 5,
+        range: None,
+    },
+    Spell {
+        name: "Heal",
+        mana_cost: 4,
+        range: Some(6),
+    },
+    Spell {
+        name: "Fire Breath",
+        mana_cost: 10,
+        range: Some(5),
+    },
+];
+
+pub fn spell_index(name: &str) -> Option<usize> {
+    SPELLS.iter().position(|s| s.name == name)
+}
+
+/// Turns cast intents into uses of a throwaway item carrying the spell's effect, so spells
+/// are resolved by `ItemUseSystem` exactly like the matching scroll. Needs the whole world to
+/// build the effect, so it runs between systems rather than as one.
+///
+/// Casters with `KnownSpells` can only cast what they know, and casters with `Mana` pay for
+/// it; monster abilities have neither and are limited by their cooldowns instead.
+pub fn cast_spells(ecs: &mut World) {
+    let mut casts: Vec<(Entity, String, Option<rltk::Point>)> = Vec::new();
+    {
+        let player_entity = ecs.fetch::<Entity>();
+        let entities = ecs.entities();
+        let mut wants_cast = ecs.write_storage::<WantsToCastSpell>();
+        let known_spells = ecs.read_storage::<KnownSpells>();
+        let mut mana = ecs.write_storage::<Mana>();
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let map = ecs.fetch::<Map>();
+        let mut log = ecs.write_resource::<GameLog>();
+        let mut run_stats = ecs.write_resource::<RunStatistics>();
+
+        for (entity, cast) in (&entities, &wants_cast).join() {
+            let spell = match spell_index(&cast.spell) {
+                Some(index) => &SPELLS[index],
+                None => continue,
+            };
+            if let Some(known) = known_spells.get(entity) {
+                if !known.spells.contains(&cast.spell) {
+                    continue;
+                }
+            }
+            if let Some(mana) = mana.get_mut(entity) {
+                if mana.current < spell.mana_cost {
+                    if entity == *player_entity {
+                        log.push(
+                            LogCategory::System,
+                            format!("You don't have enough mana to cast {}.", spell.name),
+                        );
+                    }
+                    continue;
+                }
+                mana.current -= spell.mana_cost;
+            }
+            if entity == *player_entity {
+                log.push(LogCategory::Combat, format!("You cast {}.", spell.name));
+                morgue::tally(&mut run_stats.spells_cast, spell.name);
+            } else if let (Some(name), Some(pos)) = (names.get(entity), positions.get(entity)) {
+                if map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                    log.add(
+                        LogMessage::new(LogCategory::Combat)
+                            .text("The ")
+                            .npc(&name.name)
+                            .text(format!(" casts {}!", spell.name)),
+                    );
+                }
+            }
+            casts.push((entity, cast.spell.clone(), cast.target));
+        }
+        wants_cast.clear();
+    }
+
+    for (caster, spell, target) in casts {
+        if let Some(effect) = spawner::spell_effect(ecs, &spell) {
+            ecs.write_storage::<WantsToUseItem>()
+                .insert(
+                    caster,
+                    WantsToUseItem {
+                        item: effect,
+                        target,
+                    },
+                )
+                .expect("Unable to insert intent");
+        }
+    }
+}
+
+/// Spawns minions called by monster abilities, on free tiles around the summoner.
+pub fn summon_minions(ecs: &mut World) {
+    let mut spawns: Vec<(usize, String)> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let mut wants_summon = ecs.write_storage::<WantsToSummon>();
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<rltk::Point>();
+        let mut log = ecs.write_resource::<GameLog>();
+
+        for (entity, summon, pos) in (&entities, &wants_summon, &positions).join() {
+            let mut placed = 0;
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (x, y) = (pos.x + dx, pos.y + dy);
+                    if placed >= summon.count
+                        || x < 1
+                        || x > map.width - 2
+                        || y < 1
+                        || y > map.height - 2
+                    {
+                        continue;
+                    }
+                    let idx = map.xy_idx(x, y);
+                    let occupied = map.blocked_tiles[idx]
+                        || (x == player_pos.x && y == player_pos.y)
+                        || spawns.iter().any(|(i, _)| *i == idx);
+                    if !occupied {
+                        spawns.push((idx, summon.minion.clone()));
+                        placed += 1;
+                    }
+                }
+            }
+            if placed > 0 && map.visible_tiles[map.xy_idx(pos.x, pos.y)] {
+                if let Some(name) = names.get(entity) {
+                    log.add(
+                        LogMessage::new(LogCategory::Combat)
+                            .text("The ")
+                            .npc(&name.name)
+                            .text(" calls for help!"),
+                    );
+                }
+            }
+        }
+        wants_summon.clear();
+    }
+
+    for (idx, minion) in spawns {
+        spawner::spawn_entity(ecs, idx, &minion);
+    }
+}
+
+pub struct ManaSystem {}
+
+impl<'a> System<'a> for ManaSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Mana>,
+        ReadExpect<'a, Entity>, // The player
+        ReadExpect<'a, RunState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut mana, player_entity, runstate) = data;
+
+        for (entity, mana) in (&entities, &mut mana).join() {
+            let proceed = match *runstate {
+                RunState::PlayerTurn => entity == *player_entity,
+                RunState::MonsterTurn => entity != *player_entity,
+                _ => false,
+            };
+
+            if proceed && mana.current < mana.max {
+                mana.regen_timer -= 1;
+                if mana.regen_timer < 1 {
+                    mana.current += 1;
+                    mana.regen_timer = MANA_REGEN_TURNS;
+                }
+            }
+        }
+    }
+}
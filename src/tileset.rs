@@ -0,0 +1,151 @@
+use super::{map, palette::Palette, Hidden, Map, Name, Position, Renderable};
+use rltk::{FontCharType, Rltk, RGB};
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+
+rltk::embedded_resource!(EXAMPLE_TILES, "../resources/example_tiles.jpg");
+
+const TILESET_FILE: &str = "./resources/tileset.json";
+const DEFAULT_TILESET: &str = include_str!("../resources/tileset.json");
+
+/// In tile mode the map is drawn on the bottom layer, in the tileset's font, and everything
+/// else (text, and anything without a tile) on the layer above.
+pub const TILE_LAYER: usize = 0;
+pub const TEXT_LAYER: usize = 1;
+
+/// Which tile in the tileset stands for what. Anything left out is drawn as text instead.
+#[derive(Deserialize)]
+pub struct Tileset {
+    /// The tileset image, looked for in the resources folder.
+    pub font: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    #[serde(default)]
+    floor: Option<FontCharType>,
+    #[serde(default)]
+    wall: Option<FontCharType>,
+    #[serde(default)]
+    down_stairs: Option<FontCharType>,
+    /// Sixteen wall tiles, one for each combination of neighbouring walls, in the same order
+    /// as the text walls in `map::wall_glyph`. Empty to use `wall` everywhere.
+    #[serde(default)]
+    walls: Vec<FontCharType>,
+    /// Tiles for entities, by name.
+    #[serde(default)]
+    entities: HashMap<String, FontCharType>,
+}
+
+impl Tileset {
+    /// Reads the mapping file, falling back to the built-in one (and saying why) if it can't.
+    pub fn load() -> (Tileset, Option<String>) {
+        rltk::link_resource!(EXAMPLE_TILES, "resources/example_tiles.jpg");
+
+        let default = || serde_json::from_str(DEFAULT_TILESET).expect("Bad built-in tileset");
+        match fs::read_to_string(TILESET_FILE) {
+            Err(_) => (default(), None),
+            Ok(data) => match serde_json::from_str(&data) {
+                Ok(tileset) => (tileset, None),
+                Err(e) => (
+                    default(),
+                    Some(format!("Could not read {}: {}", TILESET_FILE, e)),
+                ),
+            },
+        }
+    }
+
+    fn map_tile(&self, map: &Map, idx: usize) -> Option<FontCharType> {
+        match map.tiles[idx] {
+            map::TileType::Floor => self.floor,
+            map::TileType::DownStairs => self.down_stairs,
+            map::TileType::Wall => {
+                let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+                map::wall_mask(map, x, y)
+                    .and_then(|mask| self.walls.get(mask as usize).copied())
+                    .or(self.wall)
+            }
+        }
+    }
+
+    fn entity_tile(&self, name: Option<&Name>) -> Option<FontCharType> {
+        name.and_then(|name| self.entities.get(&name.name).copied())
+    }
+}
+
+/// Clears the whole screen; in tile mode, that means both layers.
+pub fn clear_screen(ctx: &mut Rltk) {
+    if ctx.active_console == TEXT_LAYER {
+        ctx.set_active_console(TILE_LAYER);
+        ctx.cls();
+        ctx.set_active_console(TEXT_LAYER);
+    }
+    ctx.cls();
+}
+
+fn set_tile(ctx: &mut Rltk, x: i32, y: i32, tint: RGB, tile: FontCharType) {
+    ctx.set_active_console(TILE_LAYER);
+    ctx.set(x, y, tint, RGB::named(rltk::BLACK), tile);
+    ctx.set_active_console(TEXT_LAYER);
+}
+
+/// Draws the map and what the player can see on it, using tiles where the tileset has them.
+/// Only the topmost thing in each cell is drawn, since text always shows over tiles.
+pub fn draw_world(ecs: &World, ctx: &mut Rltk) {
+    let tileset = ecs.fetch::<Tileset>();
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let names = ecs.read_storage::<Name>();
+    let palette = ecs.fetch::<Palette>();
+
+    let mut data = (&entities, &positions, &renderables, !&hidden)
+        .join()
+        .collect::<Vec<_>>();
+    data.sort_by_key(|d| std::cmp::Reverse(d.2.render_order));
+    let mut on_top = HashMap::new();
+    for (entity, pos, render, _hidden) in data.iter() {
+        let idx = map.xy_idx(pos.x, pos.y);
+        if map.visible_tiles[idx] {
+            on_top.insert(idx, (*entity, *render));
+        }
+    }
+
+    for idx in 0..map.tiles.len() {
+        if !map.revealed_tiles[idx] {
+            continue;
+        }
+        let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+        if let Some((entity, render)) = on_top.get(&idx) {
+            match tileset.entity_tile(names.get(*entity)) {
+                Some(tile) => set_tile(ctx, x, y, RGB::named(rltk::WHITE), tile),
+                None => ctx.set(
+                    x,
+                    y,
+                    palette.entity_fg(ecs, *entity, render),
+                    render.bg,
+                    render.glyph,
+                ),
+            }
+            continue;
+        }
+        match tileset.map_tile(&map, idx) {
+            Some(tile) => {
+                let tint = if !map.visible_tiles[idx] {
+                    RGB::named(rltk::GRAY)
+                } else if map.bloodstains.contains(&idx) {
+                    RGB::from_f32(1.0, 0.6, 0.6)
+                } else {
+                    RGB::named(rltk::WHITE)
+                };
+                set_tile(ctx, x, y, tint, tile);
+            }
+            None => {
+                let (glyph, fg, bg) = map::tile_glyph(&map, &palette, idx);
+                ctx.set(x, y, fg, bg, glyph);
+            }
+        }
+    }
+}
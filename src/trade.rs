@@ -0,0 +1,116 @@
+use super::{
+    container, encumbrance_system,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    spawner, Encumbrance, InBackpack, InContainer, ItemValue, Name, Position, Purse,
+};
+use specs::prelude::*;
+
+/// Buys an item from a vendor, putting it straight into the player's backpack.
+/// Returns false (and logs why) if the player can't afford it or carry it.
+pub fn buy(ecs: &mut World, item_name: &str) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let price = spawner::item_value(item_name).unwrap_or(0);
+    let pounds = spawner::item_weight(item_name).unwrap_or(0);
+
+    if let Some(load) = ecs.read_storage::<Encumbrance>().get(player_entity) {
+        if load.carried + pounds > encumbrance_system::weight_limit(load.capacity) {
+            ecs.fetch_mut::<GameLog>().add(
+                LogMessage::new(LogCategory::Item)
+                    .text("The ")
+                    .item(item_name)
+                    .text(" is too heavy to carry with everything else."),
+            );
+            return false;
+        }
+    }
+
+    {
+        let mut purses = ecs.write_storage::<Purse>();
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        let purse = purses.get_mut(player_entity);
+        match purse {
+            Some(purse) if purse.gold >= price => {
+                purse.gold -= price;
+                gamelog.add(
+                    LogMessage::new(LogCategory::Item)
+                        .text("You buy the ")
+                        .item(item_name)
+                        .text(format!(" for {} gold.", price)),
+                );
+            }
+            _ => {
+                gamelog.add(
+                    LogMessage::new(LogCategory::Item)
+                        .text("You can't afford the ")
+                        .item(item_name)
+                        .text("."),
+                );
+                return false;
+            }
+        }
+    }
+
+    let idx = {
+        let positions = ecs.read_storage::<Position>();
+        let map = ecs.fetch::<super::Map>();
+        let pos = positions.get(player_entity).unwrap();
+        map.xy_idx(pos.x, pos.y)
+    };
+    if let Some(item) = spawner::spawn_entity(ecs, idx, item_name) {
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(
+                item,
+                InBackpack {
+                    owner: player_entity,
+                },
+            )
+            .expect("Unable to insert backpack entry");
+    }
+    if let Some(load) = ecs.write_storage::<Encumbrance>().get_mut(player_entity) {
+        load.carried += pounds;
+    }
+    true
+}
+
+/// What a vendor pays for an item: half of its listed value.
+pub fn sell_price(value: &ItemValue) -> i32 {
+    value.gold / 2
+}
+
+/// Sells an item from the player's backpack to a vendor.
+pub fn sell(ecs: &mut World, item: Entity) {
+    let player_entity = *ecs.fetch::<Entity>();
+    {
+        let values = ecs.read_storage::<ItemValue>();
+        let names = ecs.read_storage::<Name>();
+        let mut purses = ecs.write_storage::<Purse>();
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+        let price = values.get(item).map(sell_price).unwrap_or(0);
+        if let Some(purse) = purses.get_mut(player_entity) {
+            purse.gold += price;
+        }
+        if let Some(name) = names.get(item) {
+            gamelog.add(
+                LogMessage::new(LogCategory::Item)
+                    .text("You sell the ")
+                    .item(&name.name)
+                    .text(format!(" for {} gold.", price)),
+            );
+        }
+    }
+    // Anything in a sold bag goes back into the backpack first
+    for inside in container::contents(ecs, item) {
+        ecs.write_storage::<InContainer>().remove(inside);
+        ecs.write_storage::<InBackpack>()
+            .insert(
+                inside,
+                InBackpack {
+                    owner: player_entity,
+                },
+            )
+            .expect("Unable to insert backpack entry");
+    }
+    ecs.delete_entity(item).expect("Unable to delete sold item");
+}
@@ -1,6 +1,10 @@
 use super::{
-    gamelog::GameLog, particle_system::ParticleBuilder, EntityMoved, EntryTrigger, Hidden,
-    InflictsDamage, Map, Name, Position, SingleActivation, SufferDamage,
+    gamelog::{GameLog, LogCategory, LogMessage},
+    morgue::RunStatistics,
+    palette::Colour,
+    particle_system::ParticleBuilder,
+    EntityMoved, EntryTrigger, Hidden, InflictsDamage, Map, Name, Position, SingleActivation,
+    SufferDamage,
 };
 use specs::prelude::*;
 
@@ -21,6 +25,8 @@ impl<'a> System<'a> for TriggerSystem {
         WriteExpect<'a, ParticleBuilder>,
         WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, SingleActivation>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStatistics>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -37,6 +43,8 @@ impl<'a> System<'a> for TriggerSystem {
             mut particle_builder,
             mut inflict_damage,
             single_activation,
+            player_entity,
+            mut run_stats,
         ) = data;
 
         // Iterate the entities that moved and their final position
@@ -53,10 +61,17 @@ impl<'a> System<'a> for TriggerSystem {
                             // We triggered it
                             let name = names.get(*entity_id);
                             if let Some(name) = name {
-                                log.entries.push(format!("{} triggers!", &name.name));
+                                log.add(
+                                    LogMessage::new(LogCategory::Trap)
+                                        .item(&name.name)
+                                        .text(" triggers!"),
+                                );
                             }
 
                             hidden.remove(*entity_id); // The trap is no longer hidden
+                            if entity == *player_entity {
+                                run_stats.traps_triggered += 1;
+                            }
 
                             // If the trap is damage inflicting, do it
                             let damage = inflicts_damage.get(*entity_id);
@@ -64,7 +79,7 @@ impl<'a> System<'a> for TriggerSystem {
                                 particle_builder.request(
                                     pos.x,
                                     pos.y,
-                                    rltk::RGB::named(rltk::ORANGE),
+                                    Colour::Warning,
                                     rltk::RGB::named(rltk::BLACK),
                                     rltk::to_cp437('‼'),
                                     200.0,
@@ -73,7 +88,13 @@ impl<'a> System<'a> for TriggerSystem {
                                     &mut inflict_damage,
                                     entity,
                                     damage.damage,
+                                    false,
                                 );
+                                if entity == *player_entity {
+                                    if let Some(name) = name {
+                                        run_stats.last_hurt_by = Some(name.name.clone());
+                                    }
+                                }
                             }
 
                             // If it is single activation, it needs to be removed
@@ -1,4 +1,8 @@
-use super::{gamelog::GameLog, Hidden, Map, Name, Player, Position, Viewshed};
+use super::{
+    gamelog::{GameLog, LogCategory, LogMessage},
+    morgue::RunStatistics,
+    Hidden, Map, Name, Player, Position, Viewshed,
+};
 use rltk::{field_of_view, Point};
 use specs::prelude::*;
 
@@ -16,11 +20,22 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteExpect<'a, rltk::RandomNumberGenerator>,
         WriteExpect<'a, GameLog>,
         ReadStorage<'a, Name>,
+        WriteExpect<'a, RunStatistics>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player, mut hidden, mut rng, mut log, names) =
-            data;
+        let (
+            mut map,
+            entities,
+            mut viewshed,
+            pos,
+            player,
+            mut hidden,
+            mut rng,
+            mut log,
+            names,
+            mut run_stats,
+        ) = data;
 
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
@@ -39,6 +54,9 @@ impl<'a> System<'a> for VisibilitySystem {
                     }
                     for vis in viewshed.visible_tiles.iter() {
                         let idx = map.xy_idx(vis.x, vis.y);
+                        if !map.revealed_tiles[idx] {
+                            run_stats.tiles_revealed += 1;
+                        }
                         map.revealed_tiles[idx] = true;
                         map.visible_tiles[idx] = true;
 
@@ -49,7 +67,12 @@ impl<'a> System<'a> for VisibilitySystem {
                                 if rng.roll_dice(1, 24) == 1 {
                                     let name = names.get(*e);
                                     if let Some(name) = name {
-                                        log.entries.push(format!("You spotted a {}.", &name.name));
+                                        log.add(
+                                            LogMessage::new(LogCategory::Trap)
+                                                .text("You spotted a ")
+                                                .item(&name.name)
+                                                .text("."),
+                                        );
                                     }
                                     hidden.remove(*e);
                                 }